@@ -0,0 +1,79 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use animation::{clip::Clip, frame::Frame, interpolation::Interpolation, pose::Pose, track::Track};
+use glam::{Quat, Vec3};
+use math::glam_transform::Transform;
+
+const JOINTS: usize = 60;
+const FRAMES: usize = 120;
+const FRAME_RATE: f32 = 30.0;
+const CHARACTERS: usize = 100;
+const UPDATES: usize = 60;
+
+fn build_clip() -> Clip {
+    let mut clip = Clip::new(Some("Benchmark"));
+    for joint in 0..JOINTS {
+        let track = clip.transform_track(joint as u32);
+        let times = (0..FRAMES).map(|i| i as f32 / FRAME_RATE);
+        track.position = Track::new_with_args(
+            Interpolation::Linear,
+            times
+                .clone()
+                .map(|t| Frame::new_simple(t, Vec3::new(t.sin(), t.cos(), t)))
+                .collect(),
+        );
+        track.rotation = Track::new_with_args(
+            Interpolation::Linear,
+            times
+                .map(|t| Frame::new_simple(t, Quat::from_rotation_y(t)))
+                .collect(),
+        );
+    }
+    clip.recalculate_duration();
+    clip
+}
+
+fn build_pose() -> Pose {
+    let mut pose = Pose::new();
+    for joint in 0..JOINTS {
+        pose.add_local_transform(Transform::default());
+        pose.add_parent(joint.checked_sub(1));
+    }
+    pose
+}
+
+fn run(clip: &Clip) -> Duration {
+    let mut poses = vec![build_pose(); CHARACTERS];
+    let start = Instant::now();
+    for update in 0..UPDATES {
+        for (character, pose) in poses.iter_mut().enumerate() {
+            let time = update as f32 / 60.0 + character as f32 * 0.037;
+            black_box(clip.sample(pose, time));
+        }
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let clip = build_clip();
+    let mut optimized = clip.clone();
+    optimized.optimize(60.0);
+
+    // Warm up
+    run(&clip);
+    run(&optimized);
+
+    let linear_scan = run(&clip);
+    let lookup = run(&optimized);
+    println!(
+        "Sampling {} joints x {} frames for {} characters over {} updates",
+        JOINTS, FRAMES, CHARACTERS, UPDATES
+    );
+    println!("Linear scan:  {:?}", linear_scan);
+    println!("Lookup table: {:?}", lookup);
+    println!(
+        "Speedup:      {:.2}x",
+        linear_scan.as_secs_f64() / lookup.as_secs_f64()
+    );
+}
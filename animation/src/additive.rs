@@ -0,0 +1,159 @@
+use super::{bone_mask::BoneMask, clip::Clip, pose::Pose, skeleton::Skeleton};
+
+/// The pose an additive clip is measured against
+#[derive(Clone)]
+pub enum AdditiveReference {
+    FirstFrame,
+    Time(f32),
+    RestPose,
+    /// A frame of another clip, e.g. the idle the additive clip was authored on top of
+    Clip(Box<Clip>, f32),
+}
+
+impl AdditiveReference {
+    pub fn pose(&self, skeleton: &Skeleton, clip: &Clip) -> Pose {
+        let mut result = skeleton.rest_pose.clone();
+        match self {
+            AdditiveReference::FirstFrame => {
+                clip.sample(&mut result, clip.start_time);
+            }
+            AdditiveReference::Time(time) => sample_without_looping(clip, &mut result, *time),
+            AdditiveReference::RestPose => {}
+            AdditiveReference::Clip(reference, time) => {
+                sample_without_looping(reference, &mut result, *time)
+            }
+        }
+        result
+    }
+}
+
+/// A looping clip wraps its end time around to the start
+fn sample_without_looping(clip: &Clip, out_pose: &mut Pose, time: f32) {
+    let mut clip = clip.clone();
+    clip.looping = false;
+    clip.sample(out_pose, time);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdditiveDriver {
+    /// Plays the clip like any other, scaled by `speed`
+    Time { time: f32, speed: f32 },
+    /// Picks the frame at a normalized position in the clip, e.g. an aim offset driven by
+    /// where the character is looking
+    Parameter(f32),
+}
+
+#[derive(Clone)]
+pub struct AdditiveLayer {
+    pub clip: Clip,
+    pub base: Pose,
+    pub weight: f32,
+    pub mask: Option<BoneMask>,
+    pub driver: AdditiveDriver,
+    pose: Pose,
+}
+
+impl AdditiveLayer {
+    pub fn new(
+        skeleton: &Skeleton,
+        clip: Clip,
+        reference: &AdditiveReference,
+        driver: AdditiveDriver,
+    ) -> Self {
+        Self {
+            base: reference.pose(skeleton, &clip),
+            pose: skeleton.rest_pose.clone(),
+            clip,
+            weight: 1.0,
+            mask: None,
+            driver,
+        }
+    }
+
+    /// Moves the layer forward and samples its clip
+    fn update(&mut self, delta_time: f32) {
+        match &mut self.driver {
+            AdditiveDriver::Time { time, speed } => {
+                *time = self
+                    .clip
+                    .sample(&mut self.pose, *time + delta_time * *speed);
+            }
+            AdditiveDriver::Parameter(parameter) => {
+                let time = self.clip.start_time + self.clip.duration() * parameter.clamp(0.0, 1.0);
+                // Sampled without looping so a parameter of 1 reaches the last frame
+                let looping = self.clip.looping;
+                self.clip.looping = false;
+                self.clip.sample(&mut self.pose, time);
+                self.clip.looping = looping;
+            }
+        }
+    }
+}
+
+/// Additive layers applied in order on top of a base pose
+#[derive(Clone, Default)]
+pub struct AdditiveStack {
+    layers: Vec<AdditiveLayer>,
+}
+
+impl AdditiveStack {
+    pub fn new() -> Self {
+        Self { layers: vec![] }
+    }
+
+    pub fn add_layer(&mut self, layer: AdditiveLayer) -> usize {
+        self.layers.push(layer);
+        self.layers.len() - 1
+    }
+
+    pub fn layer(&self, idx: usize) -> Option<&AdditiveLayer> {
+        self.layers.get(idx)
+    }
+
+    pub fn layer_mut(&mut self, idx: usize) -> Option<&mut AdditiveLayer> {
+        self.layers.get_mut(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn set_weight(&mut self, idx: usize, weight: f32) {
+        if let Some(layer) = self.layers.get_mut(idx) {
+            layer.weight = weight;
+        }
+    }
+
+    /// Sets the parameter of a parameter driven layer
+    pub fn set_parameter(&mut self, idx: usize, parameter: f32) {
+        if let Some(layer) = self.layers.get_mut(idx) {
+            if let AdditiveDriver::Parameter(value) = &mut layer.driver {
+                *value = parameter;
+            }
+        }
+    }
+
+    /// Advances every layer and adds it to `pose`
+    pub fn apply(&mut self, pose: &mut Pose, delta_time: f32) {
+        for layer in &mut self.layers {
+            layer.update(delta_time);
+            if layer.weight <= 0.0 {
+                continue;
+            }
+            match &layer.mask {
+                Some(mask) => {
+                    let mut mask = mask.clone();
+                    mask.scale(layer.weight);
+                    pose.add_masked(&pose.clone(), &layer.pose, &layer.base, &mask);
+                }
+                None => {
+                    pose.add_weighted(&pose.clone(), &layer.pose, &layer.base, layer.weight, None)
+                }
+            }
+        }
+    }
+}
@@ -6,7 +6,7 @@ use math::{quaternion::Quaternion, vector3::Vector3};
 
 pub trait ArrayType {
     const LENGTH: usize;
-    type Slice: AsRef<[f32]> + AsMut<[f32]> + Debug + Clone;
+    type Slice: AsRef<[f32]> + AsMut<[f32]> + Debug + Clone + PartialEq;
 
     fn from_slice(array: &Self::Slice) -> Self;
     fn to_slice(&self) -> Self::Slice;
@@ -0,0 +1,142 @@
+use glam::{Quat, Vec3};
+
+use super::{
+    clip::Clip,
+    frame::Frame,
+    interpolation::Interpolation,
+    pose::Pose,
+    skeleton::Skeleton,
+    track::{ScalarTrack, Track},
+    transform_track::TransformTrack,
+    weights_track::WeightsTrack,
+};
+
+/// Bakes `clip` into linear tracks with keys spaced evenly at `sample_rate` frames per second.
+/// Panics if `sample_rate` isn't a positive finite number
+pub fn bake(clip: &Clip, skeleton: &Skeleton, sample_rate: f32) -> Clip {
+    bake_with(clip, skeleton, sample_rate, |_, _| {})
+}
+
+/// Like `bake` but lets `modify` change the pose of every frame before it is stored, e.g. to
+/// solve IK or add an additive clip on top. Joints `modify` moves away from the rest pose get
+/// tracks of their own. See [`Clip::extract_root_motion`] about root motion
+pub fn bake_with<F>(clip: &Clip, skeleton: &Skeleton, sample_rate: f32, mut modify: F) -> Clip
+where
+    F: FnMut(&mut Pose, f32),
+{
+    let times = sample_times(clip, sample_rate);
+    let rest_pose = &skeleton.rest_pose;
+    let poses = sample_poses(clip, rest_pose, &times, |pose, time| {
+        modify(pose, time);
+        pose.clone()
+    });
+
+    let animated = (0..rest_pose.len()).filter(|&joint| {
+        clip.tracks().iter().any(|t| t.id as usize == joint)
+            || poses
+                .iter()
+                .any(|p| p.local_transform(joint) != rest_pose.local_transform(joint))
+    });
+    let mut baked = clip_from_poses(clip, animated, &times, &poses);
+
+    let mut source = clip.clone();
+    source.looping = false;
+    for weights_track in clip.weights_tracks() {
+        let mut weights = vec![vec![]; weights_track.weights.len()];
+        let mut out_weights = vec![0.0; weights_track.weights.len()];
+        for &time in &times {
+            source.sample_weights(weights_track.id, &mut out_weights, time);
+            for (frames, weight) in weights.iter_mut().zip(&out_weights) {
+                frames.push(Frame::new_simple(time, *weight));
+            }
+        }
+        let mut track = WeightsTrack::new(weights_track.id);
+        track.weights = weights
+            .into_iter()
+            .map(|frames| ScalarTrack::new_with_args(Interpolation::Linear, frames))
+            .collect();
+        baked.add_weights_track(track);
+    }
+    baked
+}
+
+/// Samples `clip` on top of `rest_pose` at every one of `times`, `convert` turns each sampled
+/// pose into the one that is kept
+pub(crate) fn sample_poses<F>(
+    clip: &Clip,
+    rest_pose: &Pose,
+    times: &[f32],
+    mut convert: F,
+) -> Vec<Pose>
+where
+    F: FnMut(&mut Pose, f32) -> Pose,
+{
+    // Sample without looping so the last key holds the end of the clip rather than its start
+    let mut source = clip.clone();
+    source.looping = false;
+
+    let mut pose = rest_pose.clone();
+    let mut poses = Vec::with_capacity(times.len());
+    for &time in times {
+        pose.clone_from(rest_pose);
+        source.sample(&mut pose, time);
+        poses.push(convert(&mut pose, time));
+    }
+    poses
+}
+
+/// Clip with the name, looping and events of `clip` and a track from `poses` for every joint
+/// in `joints`
+pub(crate) fn clip_from_poses<I>(clip: &Clip, joints: I, times: &[f32], poses: &[Pose]) -> Clip
+where
+    I: IntoIterator<Item = usize>,
+{
+    let mut result = Clip::new(Some(&clip.name));
+    result.looping = clip.looping;
+    for joint in joints {
+        result.add_track(track_from_poses(joint, times, poses));
+    }
+    for event in clip.events() {
+        result.add_event(event.time, &event.name);
+    }
+    result.recalculate_duration();
+    result
+}
+
+/// Linear track of `joint` keyed at `times` with the local transforms from `poses`
+pub(crate) fn track_from_poses(joint: usize, times: &[f32], poses: &[Pose]) -> TransformTrack {
+    let mut track = TransformTrack::new(joint as u32);
+    let mut position = vec![];
+    let mut rotation: Vec<Frame<Quat>> = vec![];
+    let mut scale = vec![];
+    for (time, pose) in times.iter().zip(poses) {
+        let local = pose.local_transform(joint);
+        position.push(Frame::new_simple(*time, local.translation));
+        // Keep consecutive keys in the same hemisphere so the track never takes the long way
+        let mut r = local.rotation;
+        if let Some(previous) = rotation.last() {
+            if Quat::from_array(previous.value).dot(r) < 0.0 {
+                r = -r;
+            }
+        }
+        rotation.push(Frame::new_simple(*time, r));
+        scale.push(Frame::new_simple(*time, local.scale));
+    }
+    track.position = Track::<Vec3>::new_with_args(Interpolation::Linear, position);
+    track.rotation = Track::new_with_args(Interpolation::Linear, rotation);
+    track.scale = Track::<Vec3>::new_with_args(Interpolation::Linear, scale);
+    track
+}
+
+/// Evenly spaced times from the start to the end of the clip, the last one lands on the end
+pub(crate) fn sample_times(clip: &Clip, sample_rate: f32) -> Vec<f32> {
+    assert!(
+        sample_rate.is_finite() && sample_rate > 0.0,
+        "Sample rate must be positive, got {sample_rate}"
+    );
+    let duration = clip.duration();
+    let frames = (duration * sample_rate).ceil().max(1.0) as usize;
+    (0..=frames)
+        .map(|i| (clip.start_time + i as f32 / sample_rate).min(clip.start_time + duration))
+        .collect()
+}
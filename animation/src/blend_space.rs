@@ -0,0 +1,276 @@
+use glam::Vec2;
+
+use super::{clip::Clip, pose::Pose, skeleton::Skeleton};
+
+/// Clips of a blend space played with a shared normalized phase
+struct PhaseSyncedClips {
+    clips: Vec<Clip>,
+    poses: Vec<Pose>,
+    rest_pose: Pose,
+    phase: f32,
+    pose: Pose,
+}
+
+impl PhaseSyncedClips {
+    fn new(skeleton: &Skeleton) -> Self {
+        Self {
+            clips: vec![],
+            poses: vec![],
+            rest_pose: skeleton.rest_pose.clone(),
+            phase: 0.0,
+            pose: skeleton.rest_pose.clone(),
+        }
+    }
+
+    fn insert(&mut self, idx: usize, clip: Clip) {
+        self.clips.insert(idx, clip);
+        self.poses.insert(idx, self.rest_pose.clone());
+    }
+
+    fn update(&mut self, weights: &[(usize, f32)], delta_time: f32) -> &Pose {
+        let duration: f32 = weights
+            .iter()
+            .map(|&(i, w)| self.clips[i].duration() * w)
+            .sum();
+        if duration > 0.0 {
+            self.phase = (self.phase + delta_time / duration).rem_euclid(1.0);
+        }
+
+        let mut accumulated = 0.0;
+        for &(i, weight) in weights.iter().filter(|(_, w)| *w > 0.0) {
+            let clip = &self.clips[i];
+            clip.sample(
+                &mut self.poses[i],
+                clip.start_time + clip.duration() * self.phase,
+            );
+            accumulated += weight;
+            if accumulated == weight {
+                self.pose.clone_from(&self.poses[i]);
+            } else {
+                self.pose.blend(
+                    &self.pose.clone(),
+                    &self.poses[i],
+                    weight / accumulated,
+                    None,
+                );
+            }
+        }
+        &self.pose
+    }
+}
+
+/// Blends between clips placed along a single parameter, e.g. walk/jog/run by speed
+pub struct BlendSpace1D {
+    parameters: Vec<f32>,
+    clips: PhaseSyncedClips,
+}
+
+impl BlendSpace1D {
+    pub fn new(skeleton: &Skeleton) -> Self {
+        Self {
+            parameters: vec![],
+            clips: PhaseSyncedClips::new(skeleton),
+        }
+    }
+
+    pub fn add_clip(&mut self, parameter: f32, clip: Clip) {
+        let idx = self.parameters.partition_point(|p| *p < parameter);
+        self.parameters.insert(idx, parameter);
+        self.clips.insert(idx, clip);
+    }
+
+    pub fn len(&self) -> usize {
+        self.parameters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+
+    pub fn phase(&self) -> f32 {
+        self.clips.phase
+    }
+
+    pub fn weights(&self, parameter: f32) -> Vec<(usize, f32)> {
+        let len = self.parameters.len();
+        if len == 0 {
+            return vec![];
+        }
+        if parameter <= self.parameters[0] {
+            return vec![(0, 1.0)];
+        }
+        if parameter >= self.parameters[len - 1] {
+            return vec![(len - 1, 1.0)];
+        }
+        let next = self.parameters.partition_point(|p| *p <= parameter);
+        let prev = next - 1;
+        let range = self.parameters[next] - self.parameters[prev];
+        let t = (parameter - self.parameters[prev]) / range;
+        vec![(prev, 1.0 - t), (next, t)]
+    }
+
+    pub fn update(&mut self, parameter: f32, delta_time: f32) -> &Pose {
+        let weights = self.weights(parameter);
+        self.clips.update(&weights, delta_time)
+    }
+}
+
+/// Blends between clips placed on a plane, e.g. strafing by velocity direction.
+/// Weights are the barycentric coordinates within a Delaunay triangulation of the clip positions
+pub struct BlendSpace2D {
+    positions: Vec<Vec2>,
+    triangles: Vec<[usize; 3]>,
+    clips: PhaseSyncedClips,
+}
+
+impl BlendSpace2D {
+    pub fn new(skeleton: &Skeleton) -> Self {
+        Self {
+            positions: vec![],
+            triangles: vec![],
+            clips: PhaseSyncedClips::new(skeleton),
+        }
+    }
+
+    pub fn add_clip(&mut self, position: Vec2, clip: Clip) {
+        self.positions.push(position);
+        self.clips.insert(self.positions.len() - 1, clip);
+        self.triangles = triangulate(&self.positions);
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    pub fn phase(&self) -> f32 {
+        self.clips.phase
+    }
+
+    pub fn weights(&self, parameter: Vec2) -> Vec<(usize, f32)> {
+        let mut best: Option<([usize; 3], [f32; 3])> = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for &triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|i| self.positions[i]);
+            let weights = barycentric(parameter, a, b, c);
+            // The smallest weight is negative when outside, pick the triangle it is the least outside of
+            let score = weights[0].min(weights[1]).min(weights[2]);
+            if score > best_score {
+                best_score = score;
+                best = Some((triangle, weights));
+            }
+        }
+
+        match best {
+            Some((triangle, weights)) => {
+                let weights = weights.map(|w| w.max(0.0));
+                let sum: f32 = weights.iter().sum();
+                triangle
+                    .into_iter()
+                    .zip(weights)
+                    .map(|(i, w)| (i, w / sum))
+                    .collect()
+            }
+            // Not enough samples to form a triangle, fall back to the closest one
+            None => self
+                .positions
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    a.distance_squared(parameter)
+                        .total_cmp(&b.distance_squared(parameter))
+                })
+                .map(|(i, _)| vec![(i, 1.0)])
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn update(&mut self, parameter: Vec2, delta_time: f32) -> &Pose {
+        let weights = self.weights(parameter);
+        self.clips.update(&weights, delta_time)
+    }
+}
+
+fn barycentric(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> [f32; 3] {
+    let v0 = b - a;
+    let v1 = c - a;
+    let v2 = p - a;
+    let d00 = v0.dot(v0);
+    let d01 = v0.dot(v1);
+    let d11 = v1.dot(v1);
+    let d20 = v2.dot(v0);
+    let d21 = v2.dot(v1);
+    let denom = d00 * d11 - d01 * d01;
+    let v = (d11 * d20 - d01 * d21) / denom;
+    let w = (d00 * d21 - d01 * d20) / denom;
+    [1.0 - v - w, v, w]
+}
+
+fn circumcircle_contains(triangle: [Vec2; 3], p: Vec2) -> bool {
+    let [a, b, c] = triangle.map(|v| v - p);
+    let det = (a.x * a.x + a.y * a.y) * (b.x * c.y - c.x * b.y)
+        - (b.x * b.x + b.y * b.y) * (a.x * c.y - c.x * a.y)
+        + (c.x * c.x + c.y * c.y) * (a.x * b.y - b.x * a.y);
+    // The sign depends on the winding of the triangle
+    let winding = (b - a).perp_dot(c - a);
+    det * winding.signum() > 0.0
+}
+
+/// Bowyer-Watson triangulation, fine for the handful of points in a blend space
+fn triangulate(points: &[Vec2]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return vec![];
+    }
+    let (min, max) = points.iter().fold((points[0], points[0]), |(min, max), p| {
+        (min.min(*p), max.max(*p))
+    });
+    let size = (max - min).max_element().max(1.0) * 20.0;
+    let center = (min + max) * 0.5;
+
+    let mut vertices = points.to_vec();
+    let first_super = vertices.len();
+    vertices.push(center + Vec2::new(-size, -size));
+    vertices.push(center + Vec2::new(size, -size));
+    vertices.push(center + Vec2::new(0.0, size));
+    let mut triangles = vec![[first_super, first_super + 1, first_super + 2]];
+
+    for (i, &p) in points.iter().enumerate() {
+        let (bad, good): (Vec<[usize; 3]>, Vec<[usize; 3]>) = triangles
+            .into_iter()
+            .partition(|t| circumcircle_contains(t.map(|v| vertices[v]), p));
+        triangles = good;
+
+        let edges: Vec<[usize; 2]> = bad
+            .iter()
+            .flat_map(|t| [[t[0], t[1]], [t[1], t[2]], [t[2], t[0]]])
+            .collect();
+        for edge in &edges {
+            let shared = edges
+                .iter()
+                .filter(|e| {
+                    (e[0] == edge[0] && e[1] == edge[1]) || (e[0] == edge[1] && e[1] == edge[0])
+                })
+                .count()
+                > 1;
+            if !shared {
+                triangles.push([edge[0], edge[1], i]);
+            }
+        }
+    }
+
+    triangles
+        .into_iter()
+        .filter(|t| t.iter().all(|v| *v < first_super))
+        .filter(|t| {
+            let [a, b, c] = t.map(|v| points[v]);
+            (b - a).perp_dot(c - a).abs() > f32::EPSILON
+        })
+        .collect()
+}
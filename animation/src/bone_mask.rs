@@ -0,0 +1,138 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{pose::Pose, skeleton::Skeleton};
+
+/// A weight per joint deciding how much of a blended or additive pose reaches it
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BoneMask {
+    weights: Vec<f32>,
+}
+
+impl BoneMask {
+    /// A mask letting nothing through
+    pub fn new(joint_count: usize) -> Self {
+        Self {
+            weights: vec![0.0; joint_count],
+        }
+    }
+
+    pub fn full(joint_count: usize) -> Self {
+        Self {
+            weights: vec![1.0; joint_count],
+        }
+    }
+
+    /// Weights for `root` and every joint below it
+    pub fn from_subtree(pose: &Pose, root: usize, weight: f32) -> Self {
+        let mut this = Self::new(pose.len());
+        this.set_subtree(pose, root, weight);
+        this
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// Joints outside the mask get no weight
+    pub fn weight(&self, joint: usize) -> f32 {
+        self.weights.get(joint).copied().unwrap_or_default()
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// Grows the mask to fit `joint`, the joints added on the way get no weight
+    pub fn set_weight(&mut self, joint: usize, weight: f32) {
+        if joint >= self.weights.len() {
+            self.weights.resize(joint + 1, 0.0);
+        }
+        self.weights[joint] = weight.clamp(0.0, 1.0);
+    }
+
+    pub fn set_joints(&mut self, joints: &[usize], weight: f32) {
+        for &joint in joints {
+            self.set_weight(joint, weight);
+        }
+    }
+
+    pub fn set_subtree(&mut self, pose: &Pose, root: usize, weight: f32) {
+        for joint in (0..pose.len()).filter(|j| pose.is_in_hierarchy(root, *j)) {
+            self.set_weight(joint, weight);
+        }
+    }
+
+    /// Sets the weight of every joint whose name matches `pattern`, where `*` matches any
+    /// sequence of characters, e.g. `*Arm*` or `mixamorig:Left*`. Returns the number of joints
+    /// that matched
+    pub fn set_matching(&mut self, skeleton: &Skeleton, pattern: &str, weight: f32) -> usize {
+        let matching: Vec<usize> = (0..skeleton.joint_names().len())
+            .filter(|j| matches_pattern(skeleton.joint_name(*j), pattern))
+            .collect();
+        self.set_joints(&matching, weight);
+        matching.len()
+    }
+
+    /// Fades the weights from `from_weight` at `from` to `to_weight` at `to`, which has to be
+    /// below `from`. Used to let e.g. the spine ease into an upper body layer
+    pub fn set_chain(
+        &mut self,
+        pose: &Pose,
+        from: usize,
+        to: usize,
+        from_weight: f32,
+        to_weight: f32,
+    ) -> bool {
+        let mut chain = vec![to];
+        let mut current = to;
+        while current != from {
+            match pose.parent(current) {
+                Some(parent) => {
+                    chain.push(parent);
+                    current = parent;
+                }
+                None => return false,
+            }
+        }
+        chain.reverse();
+        let steps = (chain.len() - 1).max(1) as f32;
+        for (i, joint) in chain.into_iter().enumerate() {
+            let t = i as f32 / steps;
+            self.set_weight(joint, from_weight + (to_weight - from_weight) * t);
+        }
+        true
+    }
+
+    /// Multiplies every weight, e.g. to play a layer at 70%
+    pub fn scale(&mut self, factor: f32) {
+        for weight in &mut self.weights {
+            *weight = (*weight * factor).clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard so the whole name has to match
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
@@ -0,0 +1,119 @@
+use glam::{Quat, Vec3};
+
+use math::{glam_transform::FromTo, glam_transform::Transform};
+
+use super::ik_solver::{bone_direction, IkSolver, JointConstraint};
+
+/// Cyclic coordinate descent, turns one joint at a time from the end of the chain so the end
+/// points at the target
+#[derive(Debug)]
+pub struct CcdSolver {
+    ik_chain: Vec<Transform>,
+    pub num_steps: u8,
+    pub threshold: f32,
+    constraints: Vec<Option<(JointConstraint, Quat)>>,
+}
+
+impl Default for CcdSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CcdSolver {
+    pub fn new() -> Self {
+        Self::new_with_args(15, 0.00001)
+    }
+
+    pub fn new_with_args(num_steps: u8, threshold: f32) -> Self {
+        Self {
+            ik_chain: vec![],
+            num_steps,
+            threshold,
+            constraints: vec![],
+        }
+    }
+
+    fn rotate_towards(&mut self, index: usize, goal: Vec3) {
+        let effector = self.global_transform(self.len() - 1).translation;
+        let world = self.global_transform(index);
+        let inv_rot = world.rotation.inverse();
+
+        let to_effector = inv_rot * (effector - world.translation);
+        let to_goal = inv_rot * (goal - world.translation);
+        if to_effector.length_squared() < f32::EPSILON || to_goal.length_squared() < f32::EPSILON {
+            return;
+        }
+
+        let delta = Quat::from_to(to_effector, to_goal);
+        self.ik_chain[index].rotation *= delta;
+
+        if let Some((constraint, rest)) = self.constraints[index] {
+            let bone = bone_direction(&self.ik_chain, index);
+            self.ik_chain[index].rotation =
+                constraint.apply(self.ik_chain[index].rotation, rest, bone);
+        }
+    }
+}
+
+impl IkSolver for CcdSolver {
+    fn resize(&mut self, len: usize) {
+        self.ik_chain.resize(len, Transform::default());
+        self.constraints.resize(len, None);
+    }
+
+    fn len(&self) -> usize {
+        self.ik_chain.len()
+    }
+
+    fn local_transform(&self, index: usize) -> &Transform {
+        &self.ik_chain[index]
+    }
+
+    fn set_local_transform(&mut self, index: usize, t: Transform) {
+        self.ik_chain[index] = t;
+    }
+
+    fn global_transform(&self, index: usize) -> Transform {
+        let mut world = self.ik_chain[index].clone();
+        for transform in self.ik_chain[0..index].iter().rev() {
+            world = transform.combine(&world);
+        }
+        world
+    }
+
+    fn set_constraint(&mut self, index: usize, constraint: JointConstraint) {
+        self.constraints[index] = Some((constraint, self.ik_chain[index].rotation));
+    }
+
+    fn remove_constraint(&mut self, index: usize) {
+        self.constraints[index] = None;
+    }
+
+    fn constraint(&self, index: usize) -> Option<JointConstraint> {
+        self.constraints[index].map(|(constraint, _)| constraint)
+    }
+
+    fn solve(&mut self, target: &Transform) -> bool {
+        if self.len() == 0 {
+            return false;
+        }
+
+        let threshold_squared = self.threshold * self.threshold;
+        let goal = target.translation;
+
+        for _ in 0..self.num_steps {
+            let effector = self.global_transform(self.len() - 1).translation;
+            if goal.distance_squared(effector) < threshold_squared {
+                return true;
+            }
+
+            for index in (0..self.len() - 1).rev() {
+                self.rotate_towards(index, goal);
+            }
+        }
+
+        let effector = self.global_transform(self.len() - 1).translation;
+        goal.distance_squared(effector) < threshold_squared
+    }
+}
@@ -1,10 +1,23 @@
+use glam::Vec3;
 use num_traits::clamp;
 
-use super::{pose::Pose, track::loop_time, transform_track::TransformTrack};
+use math::glam_transform::Transform;
 
-#[derive(Clone)]
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{
+    event::ClipEvent, pose::Pose, root_motion::RootMotion, track::loop_time,
+    transform_track::TransformTrack, weights_track::WeightsTrack,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Clip {
     tracks: Vec<TransformTrack>,
+    weights_tracks: Vec<WeightsTrack>,
+    root_motion: Option<RootMotion>,
+    events: Vec<ClipEvent>,
     pub name: String,
     pub start_time: f32,
     end_time: f32,
@@ -15,6 +28,9 @@ impl Clip {
     pub fn new(name: Option<&str>) -> Self {
         Self {
             tracks: vec![],
+            weights_tracks: vec![],
+            root_motion: None,
+            events: vec![],
             name: name.unwrap_or("No name given").to_owned(),
             start_time: 0.0,
             end_time: 0.0,
@@ -36,12 +52,29 @@ impl Clip {
         time
     }
 
+    pub fn sample_weights(&self, node: u32, out_weights: &mut [f32], time: f32) -> f32 {
+        if self.duration() == 0.0 {
+            return 0.0;
+        }
+        let time = self.adjust_time_to_fit_range(time);
+        for track in self.weights_tracks.iter().filter(|t| t.id == node) {
+            track.sample(out_weights, time, self.looping);
+        }
+        time
+    }
+
     pub fn recalculate_duration(&mut self) {
         if let Some(s) = self
             .tracks
             .iter()
             .filter(|t| t.is_valid())
             .filter_map(|t| t.start_time())
+            .chain(
+                self.weights_tracks
+                    .iter()
+                    .filter(|t| t.is_valid())
+                    .filter_map(|t| t.start_time()),
+            )
             .reduce(f32::min)
         {
             self.start_time = s;
@@ -53,6 +86,12 @@ impl Clip {
             .iter()
             .filter(|t| t.is_valid())
             .filter_map(|t| t.end_time())
+            .chain(
+                self.weights_tracks
+                    .iter()
+                    .filter(|t| t.is_valid())
+                    .filter_map(|t| t.end_time()),
+            )
             .reduce(f32::max)
         {
             self.end_time = e;
@@ -61,6 +100,19 @@ impl Clip {
         }
     }
 
+    /// Builds frame lookup tables for every track, see `Track::optimize`
+    pub fn optimize(&mut self, samples_per_second: f32) {
+        for track in &mut self.tracks {
+            track.optimize(samples_per_second);
+        }
+        for track in &mut self.weights_tracks {
+            track.optimize(samples_per_second);
+        }
+        if let Some(root_motion) = &mut self.root_motion {
+            root_motion.optimize(samples_per_second);
+        }
+    }
+
     pub fn transform_track(&mut self, joint: u32) -> &mut TransformTrack {
         let track_index = self.tracks.iter().position(|track| track.id == joint);
         match track_index {
@@ -72,10 +124,134 @@ impl Clip {
         }
     }
 
+    pub fn tracks(&self) -> &[TransformTrack] {
+        &self.tracks
+    }
+
+    pub fn weights_tracks(&self) -> &[WeightsTrack] {
+        &self.weights_tracks
+    }
+
+    pub fn end_time(&self) -> f32 {
+        self.end_time
+    }
+
+    pub fn set_end_time(&mut self, end_time: f32) {
+        self.end_time = end_time;
+    }
+
     pub fn add_track(&mut self, track: TransformTrack) {
         self.tracks.push(track);
     }
 
+    pub fn weights_track(&mut self, node: u32) -> &mut WeightsTrack {
+        let track_index = self
+            .weights_tracks
+            .iter()
+            .position(|track| track.id == node);
+        match track_index {
+            Some(idx) => &mut self.weights_tracks[idx],
+            None => {
+                self.weights_tracks.push(WeightsTrack::new(node));
+                self.weights_tracks.last_mut().unwrap()
+            }
+        }
+    }
+
+    pub fn add_weights_track(&mut self, track: WeightsTrack) {
+        self.weights_tracks.push(track);
+    }
+
+    /// Moves the horizontal translation (and optionally the yaw around `up`) of `joint` out of the
+    /// sampled pose, it's then available through `root_motion_delta`
+    /// Does nothing if the clip has no track for `joint`.
+    /// Clips made from this one by baking, mirroring, retargeting, compressing or going through
+    /// a container keep the motion in the joint's track, this has to be called on them again
+    pub fn extract_root_motion(&mut self, joint: u32, up: Vec3, extract_yaw: bool) {
+        let start_time = self.start_time;
+        let Some(track) = self.tracks.iter_mut().find(|track| track.id == joint) else {
+            return;
+        };
+        self.root_motion = Some(RootMotion::extract(track, up, extract_yaw, start_time));
+    }
+
+    pub fn root_motion(&self) -> Option<&RootMotion> {
+        self.root_motion.as_ref()
+    }
+
+    /// Root motion from playing `delta_time` seconds (which may be negative) starting at `time`,
+    /// relative to the root at `time`. Wraps around the clip as many times as needed when looping
+    pub fn root_motion_delta(&self, time: f32, delta_time: f32) -> Transform {
+        match &self.root_motion {
+            Some(root_motion) => self
+                .playback_segments(time, delta_time)
+                .into_iter()
+                .fold(Transform::default(), |result, (from, to)| {
+                    result.combine(&root_motion.delta(from, to))
+                }),
+            None => Transform::default(),
+        }
+    }
+
+    pub fn add_event(&mut self, time: f32, name: &str) {
+        let idx = self.events.partition_point(|e| e.time <= time);
+        self.events.insert(idx, ClipEvent::new(time, name));
+    }
+
+    pub fn events(&self) -> &[ClipEvent] {
+        &self.events
+    }
+
+    /// Events passed when playing `delta_time` seconds (which may be negative) starting at `time`,
+    /// in the order they are passed
+    pub fn events_between(&self, time: f32, delta_time: f32) -> impl Iterator<Item = &ClipEvent> {
+        // Events right on the ends of the clip fire when reaching them, as playback either stops
+        // or wraps around there
+        let is_end = |t: f32| t == self.start_time || t == self.end_time;
+        self.playback_segments(time, delta_time)
+            .into_iter()
+            .flat_map(move |(from, to)| {
+                let include_to = from != to && is_end(to);
+                let forward = self.events.iter().filter(move |e| {
+                    from < to && from <= e.time && (e.time < to || (include_to && e.time == to))
+                });
+                let backward = self.events.iter().rev().filter(move |e| {
+                    to < from && e.time <= from && (to < e.time || (include_to && e.time == to))
+                });
+                forward.chain(backward)
+            })
+    }
+
+    /// Splits playing `delta_time` seconds from `time` into the ranges played between loop wraps
+    fn playback_segments(&self, time: f32, delta_time: f32) -> Vec<(f32, f32)> {
+        if self.duration() <= 0.0 {
+            return vec![];
+        }
+        let mut time = self.adjust_time_to_fit_range(time);
+        if !self.looping {
+            let to = clamp(time + delta_time, self.start_time, self.end_time);
+            return vec![(time, to)];
+        }
+
+        let mut segments = vec![];
+        let mut remaining = delta_time;
+        loop {
+            let (boundary, wrapped) = if remaining >= 0.0 {
+                (self.end_time, self.start_time)
+            } else {
+                (self.start_time, self.end_time)
+            };
+            let to_boundary = boundary - time;
+            if remaining.abs() <= to_boundary.abs() {
+                segments.push((time, time + remaining));
+                return segments;
+            }
+            segments.push((time, boundary));
+            remaining -= to_boundary;
+            time = wrapped;
+        }
+    }
+
     fn adjust_time_to_fit_range(&self, mut in_time: f32) -> f32 {
         if self.looping {
             if self.duration() <= 0.0 {
@@ -0,0 +1,576 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::mem::size_of;
+use std::ops::{Add, Mul};
+
+use glam::{Quat, Vec3};
+
+use super::{
+    array_type::ArrayType,
+    clip::Clip,
+    event::ClipEvent,
+    frame::Frame,
+    interpolation::Interpolation,
+    pose::Pose,
+    skeleton::Skeleton,
+    track::Track,
+    track_helpers::{AdjustHermiteResult, Interpolate, Neighborhood},
+    transform_track::TransformTrack,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionSettings {
+    pub position_tolerance: f32,
+    /// In radians
+    pub rotation_tolerance: f32,
+    pub scale_tolerance: f32,
+    pub weight_tolerance: f32,
+    /// Max distance any joint may move in model space, tightens the tolerances of joints with long
+    /// chains below them
+    pub end_effector_tolerance: Option<f32>,
+    pub quantize_rotations: bool,
+    /// How often the compressed clip is compared to the original for the report
+    pub error_sample_rate: f32,
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        Self {
+            position_tolerance: 0.001,
+            rotation_tolerance: 0.001,
+            scale_tolerance: 0.001,
+            weight_tolerance: 0.001,
+            end_effector_tolerance: None,
+            quantize_rotations: true,
+            error_sample_rate: 60.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompressionReport {
+    pub original_size: usize,
+    pub compressed_size: usize,
+    pub original_keyframes: usize,
+    pub compressed_keyframes: usize,
+    pub dropped_tracks: usize,
+    /// Cubic tracks keep all their keyframes as `reduce_keyframes` leaves them as they are
+    pub unreduced_tracks: usize,
+    pub max_position_error: f32,
+    pub max_rotation_error: f32,
+    pub max_scale_error: f32,
+    pub max_end_effector_error: f32,
+}
+
+impl CompressionReport {
+    pub fn ratio(&self) -> f32 {
+        if self.compressed_size == 0 {
+            return 0.0;
+        }
+        self.original_size as f32 / self.compressed_size as f32
+    }
+}
+
+/// A unit quaternion stored as its three smallest components in 15 bits each, the index of the
+/// dropped largest component is kept in the top bits of the first two components
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuantizedQuat(pub [u16; 3]);
+
+const QUANTIZED_MAX: f32 = 0x7fff as f32;
+
+impl QuantizedQuat {
+    pub fn from_quat(q: Quat) -> Self {
+        let q = q.normalize().to_array();
+        let largest = (0..4)
+            .max_by(|a, b| q[*a].abs().total_cmp(&q[*b].abs()))
+            .unwrap();
+        // q and -q are the same rotation, flip so the dropped component is positive
+        let sign = if q[largest] < 0.0 { -1.0 } else { 1.0 };
+        let mut result = [0u16; 3];
+        for (out, i) in result.iter_mut().zip((0..4).filter(|i| *i != largest)) {
+            let normalized = (q[i] * sign / FRAC_1_SQRT_2).clamp(-1.0, 1.0) * 0.5 + 0.5;
+            *out = (normalized * QUANTIZED_MAX).round() as u16;
+        }
+        result[0] |= ((largest & 1) as u16) << 15;
+        result[1] |= ((largest >> 1) as u16) << 15;
+        Self(result)
+    }
+
+    pub fn to_quat(self) -> Quat {
+        let largest = (self.0[0] >> 15) as usize | ((self.0[1] >> 15) as usize) << 1;
+        let mut q = [0.0; 4];
+        let mut sum = 0.0;
+        for (value, i) in self.0.iter().zip((0..4).filter(|i| *i != largest)) {
+            let c = ((value & 0x7fff) as f32 / QUANTIZED_MAX * 2.0 - 1.0) * FRAC_1_SQRT_2;
+            q[i] = c;
+            sum += c * c;
+        }
+        q[largest] = (1.0 - sum).max(0.0).sqrt();
+        Quat::from_array(q).normalize()
+    }
+}
+
+/// Keyframes stored without the padding of `Frame`, tangents are only kept for cubic tracks
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedTrack<V> {
+    pub interp: Interpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<V>,
+    pub tangents: Vec<(V, V)>,
+}
+
+impl<V: Copy> CompressedTrack<V> {
+    fn new<T: KeyframeValue>(track: &Track<T>, convert: impl Fn(T) -> V) -> Self {
+        let interp = track.interpolation();
+        let frames = &track.frames;
+        Self {
+            interp,
+            times: frames.iter().map(|f| f.time).collect(),
+            values: frames
+                .iter()
+                .map(|f| convert(T::from_slice(&f.value)))
+                .collect(),
+            tangents: if interp == Interpolation::Cubic {
+                frames
+                    .iter()
+                    .map(|f| {
+                        (
+                            convert(T::from_slice(&f.in_tangent)),
+                            convert(T::from_slice(&f.out_tangent)),
+                        )
+                    })
+                    .collect()
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    fn to_track<T: KeyframeValue>(&self, convert: impl Fn(V) -> T) -> Track<T> {
+        let frames = self
+            .times
+            .iter()
+            .zip(&self.values)
+            .enumerate()
+            .map(|(i, (time, value))| match self.tangents.get(i) {
+                Some((in_tangent, out_tangent)) => Frame::new(
+                    *time,
+                    convert(*in_tangent),
+                    convert(*out_tangent),
+                    convert(*value),
+                ),
+                None => Frame::new(*time, T::default(), T::default(), convert(*value)),
+            })
+            .collect();
+        Track::new_with_args(self.interp, frames)
+    }
+
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    pub fn size(&self) -> usize {
+        self.times.len() * size_of::<f32>()
+            + self.values.len() * size_of::<V>()
+            + self.tangents.len() * size_of::<(V, V)>()
+    }
+}
+
+/// Cubic rotation tangents aren't unit quaternions so they can't be quantized
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompressedRotationTrack {
+    Quantized(CompressedTrack<QuantizedQuat>),
+    Full(CompressedTrack<Quat>),
+}
+
+impl CompressedRotationTrack {
+    pub fn len(&self) -> usize {
+        match self {
+            CompressedRotationTrack::Quantized(t) => t.len(),
+            CompressedRotationTrack::Full(t) => t.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            CompressedRotationTrack::Quantized(t) => t.size(),
+            CompressedRotationTrack::Full(t) => t.size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedTransformTrack {
+    pub id: u32,
+    pub position: CompressedTrack<Vec3>,
+    pub rotation: CompressedRotationTrack,
+    pub scale: CompressedTrack<Vec3>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedWeightsTrack {
+    pub id: u32,
+    pub weights: Vec<CompressedTrack<f32>>,
+}
+
+/// Storage form of a clip, `decompress` turns it back into a playable `Clip`.
+/// See [`Clip::extract_root_motion`] about root motion
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedClip {
+    pub name: String,
+    pub looping: bool,
+    /// Kept as dropped tracks can make the remaining ones shorter than the clip
+    pub start_time: f32,
+    pub end_time: f32,
+    pub tracks: Vec<CompressedTransformTrack>,
+    pub weights_tracks: Vec<CompressedWeightsTrack>,
+    pub events: Vec<ClipEvent>,
+}
+
+impl CompressedClip {
+    pub fn decompress(&self) -> Clip {
+        let mut clip = Clip::new(Some(&self.name));
+        clip.looping = self.looping;
+        for compressed in &self.tracks {
+            let mut track = TransformTrack::new(compressed.id);
+            track.position = compressed.position.to_track(|v| v);
+            track.rotation = match &compressed.rotation {
+                CompressedRotationTrack::Quantized(t) => t.to_track(QuantizedQuat::to_quat),
+                CompressedRotationTrack::Full(t) => t.to_track(|q| q),
+            };
+            track.scale = compressed.scale.to_track(|v| v);
+            clip.add_track(track);
+        }
+        for compressed in &self.weights_tracks {
+            clip.weights_track(compressed.id).weights = compressed
+                .weights
+                .iter()
+                .map(|w| w.to_track(|v| v))
+                .collect();
+        }
+        for event in &self.events {
+            clip.add_event(event.time, &event.name);
+        }
+        clip.start_time = self.start_time;
+        clip.set_end_time(self.end_time);
+        clip
+    }
+
+    pub fn size(&self) -> usize {
+        let tracks: usize = self
+            .tracks
+            .iter()
+            .map(|t| size_of::<u32>() + t.position.size() + t.rotation.size() + t.scale.size())
+            .sum();
+        let weights: usize = self
+            .weights_tracks
+            .iter()
+            .map(|t| size_of::<u32>() + t.weights.iter().map(|w| w.size()).sum::<usize>())
+            .sum();
+        tracks + weights
+    }
+
+    pub fn keyframes(&self) -> usize {
+        let tracks: usize = self
+            .tracks
+            .iter()
+            .map(|t| t.position.len() + t.rotation.len() + t.scale.len())
+            .sum();
+        let weights: usize = self
+            .weights_tracks
+            .iter()
+            .flat_map(|t| &t.weights)
+            .map(|w| w.len())
+            .sum();
+        tracks + weights
+    }
+}
+
+/// How far apart two keyframe values are, in radians for rotations
+pub trait KeyframeError {
+    fn keyframe_error(&self, other: &Self) -> f32;
+}
+
+impl KeyframeError for f32 {
+    fn keyframe_error(&self, other: &Self) -> f32 {
+        (self - other).abs()
+    }
+}
+
+impl KeyframeError for Vec3 {
+    fn keyframe_error(&self, other: &Self) -> f32 {
+        self.distance(*other)
+    }
+}
+
+impl KeyframeError for Quat {
+    fn keyframe_error(&self, other: &Self) -> f32 {
+        2.0 * self.dot(*other).abs().min(1.0).acos()
+    }
+}
+
+/// Everything a track value needs to be sampled and compared
+pub trait KeyframeValue:
+    Neighborhood
+    + AdjustHermiteResult
+    + Copy
+    + Mul<f32, Output = Self>
+    + Add<Output = Self>
+    + Default
+    + ArrayType
+    + Interpolate
+    + KeyframeError
+{
+}
+
+impl<T> KeyframeValue for T where
+    T: Neighborhood
+        + AdjustHermiteResult
+        + Copy
+        + Mul<f32, Output = T>
+        + Add<Output = T>
+        + Default
+        + ArrayType
+        + Interpolate
+        + KeyframeError
+{
+}
+
+/// Removes keyframes that can be recreated by interpolating their neighbours within `tolerance`.
+/// Cubic tracks are left as they are as removing a key would change the tangents
+pub fn reduce_keyframes<T: KeyframeValue>(track: &Track<T>, tolerance: f32) -> Track<T> {
+    let frames = &track.frames;
+    let interp = track.interpolation();
+    if frames.len() <= 2 || interp == Interpolation::Cubic {
+        return track.clone();
+    }
+
+    let value = |i: usize| T::from_slice(&frames[i].value);
+    let mut kept = vec![frames[0].clone()];
+    let mut anchor = 0;
+    for candidate in 1..frames.len() - 1 {
+        let next = candidate + 1;
+        let removable = (anchor + 1..=candidate).all(|k| {
+            let approximation = match interp {
+                Interpolation::Constant => value(anchor),
+                _ => {
+                    let t = (frames[k].time - frames[anchor].time)
+                        / (frames[next].time - frames[anchor].time);
+                    value(anchor).interpolate(&value(next), t)
+                }
+            };
+            approximation.keyframe_error(&value(k)) <= tolerance
+        });
+        if !removable {
+            kept.push(frames[candidate].clone());
+            anchor = candidate;
+        }
+    }
+    kept.push(frames[frames.len() - 1].clone());
+    Track::new_with_args(interp, kept)
+}
+
+/// True if the track holds `rest` the whole time, in which case it can be dropped
+fn is_constant<T: KeyframeValue>(track: &Track<T>, rest: T, tolerance: f32) -> bool {
+    track.frames.iter().all(|f| {
+        let tangents_flat = track.interpolation() != Interpolation::Cubic
+            || (T::from_slice(&f.in_tangent).keyframe_error(&T::default()) <= tolerance
+                && T::from_slice(&f.out_tangent).keyframe_error(&T::default()) <= tolerance);
+        tangents_flat && T::from_slice(&f.value).keyframe_error(&rest) <= tolerance
+    })
+}
+
+fn reduce_or_drop<T: KeyframeValue>(
+    track: &Track<T>,
+    rest: T,
+    tolerance: f32,
+    report: &mut CompressionReport,
+) -> Track<T> {
+    if track.len() > 0 && is_constant(track, rest, tolerance) {
+        report.dropped_tracks += 1;
+        return Track::new();
+    }
+    reduce(track, tolerance, report)
+}
+
+fn reduce<T: KeyframeValue>(
+    track: &Track<T>,
+    tolerance: f32,
+    report: &mut CompressionReport,
+) -> Track<T> {
+    if track.interpolation() == Interpolation::Cubic && track.len() > 2 {
+        report.unreduced_tracks += 1;
+    }
+    reduce_keyframes(track, tolerance)
+}
+
+/// For every joint the distance to the furthest joint below it in the rest pose
+fn descendant_distances(rest_pose: &Pose) -> Vec<f32> {
+    let globals: Vec<Vec3> = (0..rest_pose.len())
+        .map(|i| rest_pose.global_transform(i).translation)
+        .collect();
+    let mut result = vec![0.0f32; rest_pose.len()];
+    for (joint, position) in globals.iter().enumerate() {
+        let mut parent = rest_pose.parent(joint);
+        while let Some(p) = parent {
+            result[p] = result[p].max(position.distance(globals[p]));
+            parent = rest_pose.parent(p);
+        }
+    }
+    result
+}
+
+fn frame_count<T: ArrayType>(track: &Track<T>) -> usize {
+    track.frames.len()
+}
+
+fn track_size<T: ArrayType>(track: &Track<T>) -> usize {
+    track.frames.len() * size_of::<Frame<T>>()
+}
+
+/// Reduces keyframes, drops tracks that stay at the rest pose and quantizes rotations. Cubic
+/// tracks aren't reduced, `CompressionReport::unreduced_tracks` counts them. Joints without tracks keep whatever the pose held before sampling, so the compressed clip
+/// should be sampled into a pose reset to the rest pose
+pub fn compress(
+    clip: &Clip,
+    skeleton: &Skeleton,
+    settings: &CompressionSettings,
+) -> (CompressedClip, CompressionReport) {
+    let rest_pose = &skeleton.rest_pose;
+    let distances = descendant_distances(rest_pose);
+    let mut report = CompressionReport::default();
+
+    let mut tracks = vec![];
+    for track in clip.tracks() {
+        let joint = track.id as usize;
+        let rest = rest_pose.local_transform(joint);
+        let (mut position_tolerance, mut rotation_tolerance, mut scale_tolerance) = (
+            settings.position_tolerance,
+            settings.rotation_tolerance,
+            settings.scale_tolerance,
+        );
+        if let Some(end_effector_tolerance) = settings.end_effector_tolerance {
+            position_tolerance = position_tolerance.min(end_effector_tolerance);
+            if distances[joint] > 0.0 {
+                rotation_tolerance =
+                    rotation_tolerance.min(end_effector_tolerance / distances[joint]);
+                scale_tolerance = scale_tolerance.min(end_effector_tolerance / distances[joint]);
+            }
+        }
+
+        report.original_size +=
+            track_size(&track.position) + track_size(&track.rotation) + track_size(&track.scale);
+        report.original_keyframes +=
+            frame_count(&track.position) + frame_count(&track.rotation) + frame_count(&track.scale);
+
+        let position = reduce_or_drop(
+            &track.position,
+            rest.translation,
+            position_tolerance,
+            &mut report,
+        );
+        let rotation = reduce_or_drop(
+            &track.rotation,
+            rest.rotation,
+            rotation_tolerance,
+            &mut report,
+        );
+        let scale = reduce_or_drop(&track.scale, rest.scale, scale_tolerance, &mut report);
+        if position.len() == 0 && rotation.len() == 0 && scale.len() == 0 {
+            continue;
+        }
+
+        let rotation =
+            if settings.quantize_rotations && rotation.interpolation() != Interpolation::Cubic {
+                CompressedRotationTrack::Quantized(CompressedTrack::new(
+                    &rotation,
+                    QuantizedQuat::from_quat,
+                ))
+            } else {
+                CompressedRotationTrack::Full(CompressedTrack::new(&rotation, |q| q))
+            };
+        tracks.push(CompressedTransformTrack {
+            id: track.id,
+            position: CompressedTrack::new(&position, |v| v),
+            rotation,
+            scale: CompressedTrack::new(&scale, |v| v),
+        });
+    }
+
+    let mut weights_tracks = vec![];
+    for track in clip.weights_tracks() {
+        report.original_size += track.weights.iter().map(track_size).sum::<usize>();
+        report.original_keyframes += track.weights.iter().map(frame_count).sum::<usize>();
+        weights_tracks.push(CompressedWeightsTrack {
+            id: track.id,
+            weights: track
+                .weights
+                .iter()
+                .map(|w| {
+                    CompressedTrack::new(&reduce(w, settings.weight_tolerance, &mut report), |v| v)
+                })
+                .collect(),
+        });
+    }
+
+    let compressed = CompressedClip {
+        name: clip.name.clone(),
+        looping: clip.looping,
+        start_time: clip.start_time,
+        end_time: clip.end_time(),
+        tracks,
+        weights_tracks,
+        events: clip.events().to_vec(),
+    };
+    report.compressed_size = compressed.size();
+    report.compressed_keyframes = compressed.keyframes();
+    measure_error(
+        clip,
+        &compressed.decompress(),
+        skeleton,
+        settings,
+        &mut report,
+    );
+    (compressed, report)
+}
+
+fn measure_error(
+    original: &Clip,
+    compressed: &Clip,
+    skeleton: &Skeleton,
+    settings: &CompressionSettings,
+    report: &mut CompressionReport,
+) {
+    let duration = original.duration();
+    let samples = (duration * settings.error_sample_rate).ceil().max(1.0) as usize;
+    for i in 0..=samples {
+        let time = original.start_time + duration * (i as f32 / samples as f32);
+        let mut original_pose = skeleton.rest_pose.clone();
+        let mut compressed_pose = skeleton.rest_pose.clone();
+        original.sample(&mut original_pose, time);
+        compressed.sample(&mut compressed_pose, time);
+
+        for joint in 0..original_pose.len() {
+            let a = original_pose.local_transform(joint);
+            let b = compressed_pose.local_transform(joint);
+            report.max_position_error = report
+                .max_position_error
+                .max(a.translation.keyframe_error(&b.translation));
+            report.max_rotation_error = report
+                .max_rotation_error
+                .max(a.rotation.keyframe_error(&b.rotation));
+            report.max_scale_error = report.max_scale_error.max(a.scale.keyframe_error(&b.scale));
+
+            let a = original_pose.global_transform(joint).translation;
+            let b = compressed_pose.global_transform(joint).translation;
+            report.max_end_effector_error = report.max_end_effector_error.max(a.distance(b));
+        }
+    }
+}
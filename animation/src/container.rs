@@ -0,0 +1,662 @@
+use std::{borrow::Cow, fmt, mem::size_of};
+
+use glam::{Quat, Vec3};
+
+use math::glam_transform::Transform;
+
+use super::{
+    clip::Clip, compression::KeyframeValue, frame::Frame, interpolation::Interpolation, pose::Pose,
+    skeleton::Skeleton, track::Track, transform_track::TransformTrack, weights_track::WeightsTrack,
+};
+
+/// Layout, all values are little endian and 4 byte aligned:
+/// - header: magic, version, string count, string data size, joint count, clip count
+/// - string table: offset and length per string followed by the UTF-8 data
+/// - skeleton: name, parent, rest and bind transform per joint
+/// - clip table: name, offset and size per clip so clips can be read one at a time
+/// - clip data: looping flag, start and end time and track, weights track and event counts
+///   followed by the tracks and events. A track stores its interpolation, frame count and then
+///   the time, value, in tangent and out tangent of every frame as f32s
+///
+/// Version 1 has no start and end time, the range of its clips is taken from the tracks
+pub const CONTAINER_MAGIC: [u8; 4] = *b"GANM";
+pub const CONTAINER_VERSION: u32 = 2;
+
+const TRANSFORM_FLOATS: usize = 10;
+const JOINT_SIZE: usize = 2 * size_of::<u32>() + 2 * TRANSFORM_FLOATS * size_of::<f32>();
+const CLIP_ENTRY_SIZE: usize = 3 * size_of::<u32>();
+const NO_PARENT: u32 = u32::MAX;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContainerError {
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    Truncated {
+        offset: usize,
+        needed: usize,
+    },
+    InvalidString(u32),
+    InvalidUtf8(u32),
+    InvalidInterpolation(u32),
+    InvalidParent {
+        joint: usize,
+        parent: u32,
+    },
+    /// A track animates a joint the skeleton in the container doesn't have
+    InvalidJoint {
+        clip: String,
+        joint: u32,
+    },
+    InvalidClip(usize),
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::InvalidMagic => write!(f, "Not an animation container"),
+            ContainerError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported container version {}", version)
+            }
+            ContainerError::Truncated { offset, needed } => write!(
+                f,
+                "Container truncated, needed {} bytes at offset {}",
+                needed, offset
+            ),
+            ContainerError::InvalidString(index) => write!(f, "Invalid string index {}", index),
+            ContainerError::InvalidUtf8(index) => write!(f, "String {} isn't valid UTF-8", index),
+            ContainerError::InvalidInterpolation(value) => {
+                write!(f, "Invalid interpolation {}", value)
+            }
+            ContainerError::InvalidParent { joint, parent } => {
+                write!(f, "Joint {} has invalid parent {}", joint, parent)
+            }
+            ContainerError::InvalidJoint { clip, joint } => {
+                write!(f, "Clip {} animates missing joint {}", clip, joint)
+            }
+            ContainerError::InvalidClip(index) => write!(f, "Invalid clip index {}", index),
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+/// Writes `skeleton` and `clips` into a single container.
+/// See [`Clip::extract_root_motion`] about root motion
+pub fn write_container(skeleton: Option<&Skeleton>, clips: &[Clip]) -> Vec<u8> {
+    let mut strings = StringTable::default();
+    let skeleton_data = skeleton
+        .map(|skeleton| write_skeleton(skeleton, &mut strings))
+        .unwrap_or_default();
+    let clip_data: Vec<(u32, Vec<u8>)> = clips
+        .iter()
+        .map(|clip| (strings.intern(&clip.name), write_clip(clip, &mut strings)))
+        .collect();
+
+    let mut string_data = vec![];
+    let mut string_entries = vec![];
+    for string in &strings.strings {
+        string_entries.push((string_data.len() as u32, string.len() as u32));
+        string_data.extend_from_slice(string.as_bytes());
+    }
+    string_data.resize(string_data.len().next_multiple_of(4), 0);
+
+    let mut out = vec![];
+    out.extend_from_slice(&CONTAINER_MAGIC);
+    for value in [
+        CONTAINER_VERSION,
+        strings.strings.len() as u32,
+        string_data.len() as u32,
+        skeleton.map(|s| s.rest_pose.len()).unwrap_or_default() as u32,
+        clips.len() as u32,
+    ] {
+        push_u32(&mut out, value);
+    }
+    for (offset, len) in string_entries {
+        push_u32(&mut out, offset);
+        push_u32(&mut out, len);
+    }
+    out.extend_from_slice(&string_data);
+    out.extend_from_slice(&skeleton_data);
+
+    let mut clip_offset = out.len() + clip_data.len() * CLIP_ENTRY_SIZE;
+    for (name, data) in &clip_data {
+        push_u32(&mut out, *name);
+        push_u32(&mut out, clip_offset as u32);
+        push_u32(&mut out, data.len() as u32);
+        clip_offset += data.len();
+    }
+    for (_, data) in clip_data {
+        out.extend_from_slice(&data);
+    }
+    out
+}
+
+/// Reads a container without copying it, strings are borrowed and clips are only decoded
+/// when asked for
+pub struct ContainerReader<'a> {
+    data: &'a [u8],
+    version: u32,
+    strings: Vec<&'a str>,
+    joint_count: usize,
+    skeleton_offset: usize,
+    clips: Vec<ClipEntry>,
+}
+
+struct ClipEntry {
+    name: u32,
+    offset: usize,
+    size: usize,
+}
+
+impl<'a> ContainerReader<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, ContainerError> {
+        let mut cursor = Cursor::new(data, 0);
+        if cursor.bytes(4)? != CONTAINER_MAGIC {
+            return Err(ContainerError::InvalidMagic);
+        }
+        let version = cursor.u32()?;
+        if !(1..=CONTAINER_VERSION).contains(&version) {
+            return Err(ContainerError::UnsupportedVersion(version));
+        }
+        let string_count = cursor.u32()? as usize;
+        let string_data_size = cursor.u32()? as usize;
+        let joint_count = cursor.u32()? as usize;
+        let clip_count = cursor.u32()? as usize;
+
+        let mut entries = Vec::with_capacity(string_count.min(data.len()));
+        for _ in 0..string_count {
+            entries.push((cursor.u32()? as usize, cursor.u32()? as usize));
+        }
+        let string_data = cursor.bytes(string_data_size)?;
+        let strings = entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, (offset, len))| {
+                let bytes = string_data
+                    .get(offset..offset.saturating_add(len))
+                    .ok_or(ContainerError::InvalidString(i as u32))?;
+                std::str::from_utf8(bytes).map_err(|_| ContainerError::InvalidUtf8(i as u32))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let skeleton_offset = cursor.offset;
+        cursor.skip(joint_count.saturating_mul(JOINT_SIZE))?;
+
+        let mut clips = Vec::with_capacity(clip_count.min(data.len()));
+        for i in 0..clip_count {
+            let entry = ClipEntry {
+                name: cursor.u32()?,
+                offset: cursor.u32()? as usize,
+                size: cursor.u32()? as usize,
+            };
+            if entry.name as usize >= strings.len() {
+                return Err(ContainerError::InvalidString(entry.name));
+            }
+            if entry
+                .offset
+                .checked_add(entry.size)
+                .is_none_or(|end| end > data.len())
+            {
+                return Err(ContainerError::InvalidClip(i));
+            }
+            clips.push(entry);
+        }
+
+        Ok(Self {
+            data,
+            version,
+            strings,
+            joint_count,
+            skeleton_offset,
+            clips,
+        })
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.joint_count
+    }
+
+    pub fn clip_count(&self) -> usize {
+        self.clips.len()
+    }
+
+    pub fn clip_names(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.clips.iter().map(|c| self.strings[c.name as usize])
+    }
+
+    pub fn skeleton(&self) -> Result<Option<Skeleton>, ContainerError> {
+        if self.joint_count == 0 {
+            return Ok(None);
+        }
+        let mut cursor = Cursor::new(self.data, self.skeleton_offset);
+        let mut rest_pose = Pose::new();
+        let mut bind_pose = Pose::new();
+        let mut names = vec![];
+        for joint in 0..self.joint_count {
+            names.push(self.string(cursor.u32()?)?.to_owned());
+            let parent = match cursor.u32()? {
+                NO_PARENT => None,
+                parent if (parent as usize) < self.joint_count && parent as usize != joint => {
+                    Some(parent as usize)
+                }
+                parent => return Err(ContainerError::InvalidParent { joint, parent }),
+            };
+            rest_pose.add_local_transform(read_transform(&mut cursor)?);
+            rest_pose.add_parent(parent);
+            bind_pose.add_local_transform(read_transform(&mut cursor)?);
+            bind_pose.add_parent(parent);
+        }
+        // A parent cycle would make the pose loop forever
+        for joint in 0..self.joint_count {
+            let mut parent = rest_pose.parent(joint);
+            let mut steps = 0;
+            while let Some(p) = parent {
+                steps += 1;
+                if steps > self.joint_count {
+                    return Err(ContainerError::InvalidParent {
+                        joint,
+                        parent: p as u32,
+                    });
+                }
+                parent = rest_pose.parent(p);
+            }
+        }
+        Ok(Some(Skeleton::new(rest_pose, bind_pose, names)))
+    }
+
+    pub fn clip(&self, index: usize) -> Result<Clip, ContainerError> {
+        let entry = self
+            .clips
+            .get(index)
+            .ok_or(ContainerError::InvalidClip(index))?;
+        let name = self.string(entry.name)?;
+        let data = &self.data[..entry.offset + entry.size];
+        let mut cursor = Cursor::new(data, entry.offset);
+
+        let mut clip = Clip::new(Some(name));
+        clip.looping = cursor.u32()? != 0;
+        let range = match self.version {
+            1 => None,
+            _ => Some((cursor.f32()?, cursor.f32()?)),
+        };
+        let track_count = cursor.u32()?;
+        let weights_track_count = cursor.u32()?;
+        let event_count = cursor.u32()?;
+        for _ in 0..track_count {
+            let id = cursor.u32()?;
+            if self.joint_count > 0 && id as usize >= self.joint_count {
+                return Err(ContainerError::InvalidJoint {
+                    clip: name.to_owned(),
+                    joint: id,
+                });
+            }
+            let mut track = TransformTrack::new(id);
+            track.position = read_track::<Vec3>(&mut cursor)?;
+            track.rotation = read_track::<Quat>(&mut cursor)?;
+            track.scale = read_track::<Vec3>(&mut cursor)?;
+            clip.add_track(track);
+        }
+        for _ in 0..weights_track_count {
+            let mut track = WeightsTrack::new(cursor.u32()?);
+            let count = cursor.u32()?;
+            for _ in 0..count {
+                track.weights.push(read_track::<f32>(&mut cursor)?);
+            }
+            clip.add_weights_track(track);
+        }
+        for _ in 0..event_count {
+            let time = cursor.f32()?;
+            clip.add_event(time, self.string(cursor.u32()?)?);
+        }
+        match range {
+            Some((start_time, end_time)) => {
+                clip.start_time = start_time;
+                clip.set_end_time(end_time);
+            }
+            None => clip.recalculate_duration(),
+        }
+        Ok(clip)
+    }
+
+    pub fn clip_by_name(&self, name: &str) -> Option<Result<Clip, ContainerError>> {
+        let index = self.clip_names().position(|n| n == name)?;
+        Some(self.clip(index))
+    }
+
+    pub fn clips(&self) -> Result<Vec<Clip>, ContainerError> {
+        (0..self.clips.len()).map(|i| self.clip(i)).collect()
+    }
+
+    fn string(&self, index: u32) -> Result<&'a str, ContainerError> {
+        self.strings
+            .get(index as usize)
+            .copied()
+            .ok_or(ContainerError::InvalidString(index))
+    }
+}
+
+#[derive(Default)]
+struct StringTable {
+    strings: Vec<String>,
+}
+
+impl StringTable {
+    fn intern(&mut self, string: &str) -> u32 {
+        let index = match self.strings.iter().position(|s| s == string) {
+            Some(index) => index,
+            None => {
+                self.strings.push(string.to_owned());
+                self.strings.len() - 1
+            }
+        };
+        index as u32
+    }
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_f32s(out: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn push_transform(out: &mut Vec<u8>, transform: &Transform) {
+    push_f32s(out, &transform.translation.to_array());
+    push_f32s(out, &transform.rotation.to_array());
+    push_f32s(out, &transform.scale.to_array());
+}
+
+fn write_skeleton(skeleton: &Skeleton, strings: &mut StringTable) -> Vec<u8> {
+    let mut out = vec![];
+    for joint in 0..skeleton.rest_pose.len() {
+        push_u32(&mut out, strings.intern(skeleton.joint_name(joint)));
+        let parent = skeleton.rest_pose.parent(joint);
+        push_u32(&mut out, parent.map_or(NO_PARENT, |p| p as u32));
+        push_transform(&mut out, skeleton.rest_pose.local_transform(joint));
+        push_transform(&mut out, skeleton.bind_pose.local_transform(joint));
+    }
+    out
+}
+
+fn write_track<T: KeyframeValue>(out: &mut Vec<u8>, track: &Track<T>) {
+    let interp = match track.interpolation() {
+        Interpolation::Constant => 0,
+        Interpolation::Linear => 1,
+        Interpolation::Cubic => 2,
+    };
+    push_u32(out, interp);
+    push_u32(out, track.frames.len() as u32);
+    for frame in &track.frames {
+        push_f32s(out, &[frame.time]);
+        push_f32s(out, frame.value.as_ref());
+        push_f32s(out, frame.in_tangent.as_ref());
+        push_f32s(out, frame.out_tangent.as_ref());
+    }
+}
+
+fn write_clip(clip: &Clip, strings: &mut StringTable) -> Vec<u8> {
+    let mut out = vec![];
+    push_u32(&mut out, clip.looping as u32);
+    push_f32s(&mut out, &[clip.start_time, clip.end_time()]);
+    push_u32(&mut out, clip.tracks().len() as u32);
+    push_u32(&mut out, clip.weights_tracks().len() as u32);
+    push_u32(&mut out, clip.events().len() as u32);
+    for track in clip.tracks() {
+        push_u32(&mut out, track.id);
+        write_track(&mut out, &track.position);
+        write_track(&mut out, &track.rotation);
+        write_track(&mut out, &track.scale);
+    }
+    for track in clip.weights_tracks() {
+        push_u32(&mut out, track.id);
+        push_u32(&mut out, track.weights.len() as u32);
+        for weights in &track.weights {
+            write_track(&mut out, weights);
+        }
+    }
+    for event in clip.events() {
+        push_f32s(&mut out, &[event.time]);
+        push_u32(&mut out, strings.intern(&event.name));
+    }
+    out
+}
+
+fn read_transform(cursor: &mut Cursor) -> Result<Transform, ContainerError> {
+    let v = cursor.f32s(TRANSFORM_FLOATS)?;
+    Ok(Transform::new(
+        Vec3::new(v[0], v[1], v[2]),
+        Quat::from_xyzw(v[3], v[4], v[5], v[6]),
+        Vec3::new(v[7], v[8], v[9]),
+    ))
+}
+
+fn read_track<T: KeyframeValue>(cursor: &mut Cursor) -> Result<Track<T>, ContainerError> {
+    let interp = match cursor.u32()? {
+        0 => Interpolation::Constant,
+        1 => Interpolation::Linear,
+        2 => Interpolation::Cubic,
+        value => return Err(ContainerError::InvalidInterpolation(value)),
+    };
+    let frame_count = cursor.u32()? as usize;
+    let stride = 1 + 3 * T::LENGTH;
+    let floats = cursor.f32s(frame_count.saturating_mul(stride))?;
+    let frames = floats
+        .chunks_exact(stride)
+        .map(|f| {
+            let value = |i: usize| {
+                let mut slice = T::default().to_slice();
+                slice
+                    .as_mut()
+                    .copy_from_slice(&f[1 + i * T::LENGTH..1 + (i + 1) * T::LENGTH]);
+                T::from_slice(&slice)
+            };
+            Frame::new(f[0], value(1), value(2), value(0))
+        })
+        .collect();
+    Ok(Track::new_with_args(interp, frames))
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self {
+        Self { data, offset }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ContainerError> {
+        let truncated = ContainerError::Truncated {
+            offset: self.offset,
+            needed: len,
+        };
+        let end = self.offset.checked_add(len).ok_or(truncated.clone())?;
+        let bytes = self.data.get(self.offset..end).ok_or(truncated)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), ContainerError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u32(&mut self) -> Result<u32, ContainerError> {
+        let bytes = self.bytes(size_of::<u32>())?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, ContainerError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    /// Borrows the floats straight from the data when it is aligned, copies them otherwise
+    fn f32s(&mut self, count: usize) -> Result<Cow<'a, [f32]>, ContainerError> {
+        let bytes = self.bytes(count.saturating_mul(size_of::<f32>()))?;
+        if cfg!(target_endian = "little") {
+            if let Ok(floats) = bytemuck::try_cast_slice(bytes) {
+                return Ok(Cow::Borrowed(floats));
+            }
+        }
+        Ok(Cow::Owned(
+            bytes
+                .chunks_exact(size_of::<f32>())
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use math::glam_transform::Transform;
+
+    use super::*;
+
+    fn skeleton() -> Skeleton {
+        let mut pose = Pose::new();
+        for (parent, y) in [(None, 0.0), (Some(0), 1.0), (Some(1), 0.5)] {
+            pose.add_local_transform(Transform::new(
+                Vec3::new(0.0, y, 0.0),
+                Quat::from_rotation_z(y * 0.2),
+                Vec3::ONE,
+            ));
+            pose.add_parent(parent);
+        }
+        let names = ["root", "hips", "spine"].map(str::to_owned).to_vec();
+        Skeleton::new(pose.clone(), pose, names)
+    }
+
+    fn clip(name: &str, joint: u32) -> Clip {
+        let mut clip = Clip::new(Some(name));
+        let mut track = TransformTrack::new(joint);
+        track.position = Track::new_with_args(
+            Interpolation::Cubic,
+            vec![
+                Frame::new(0.0, Vec3::ZERO, Vec3::X, Vec3::Y),
+                Frame::new(0.5, Vec3::Z, Vec3::ZERO, Vec3::new(0.2, 1.0, 0.1)),
+            ],
+        );
+        track.rotation = Track::new_with_args(
+            Interpolation::Linear,
+            vec![
+                Frame::new_simple(0.0, Quat::IDENTITY),
+                Frame::new_simple(0.5, Quat::from_rotation_y(0.4)),
+            ],
+        );
+        clip.add_track(track);
+        let mut weights = WeightsTrack::new(2);
+        weights.weights = vec![Track::new_with_args(
+            Interpolation::Constant,
+            vec![Frame::new_simple(0.0, 0.0), Frame::new_simple(0.25, 1.0)],
+        )];
+        clip.add_weights_track(weights);
+        clip.add_event(0.1, "footstep");
+        clip.looping = true;
+        // Longer than the tracks so the range has to be stored
+        clip.set_end_time(0.8);
+        clip
+    }
+
+    fn read_all(data: &[u8]) -> Result<(Option<Skeleton>, Vec<Clip>), ContainerError> {
+        let reader = ContainerReader::new(data)?;
+        Ok((reader.skeleton()?, reader.clips()?))
+    }
+
+    /// Where the joint records start, after the header and the string table
+    fn skeleton_offset(data: &[u8]) -> usize {
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        24 + u32_at(8) as usize * 8 + u32_at(12) as usize
+    }
+
+    fn set_parent(data: &mut [u8], joint: usize, parent: u32) {
+        let offset = skeleton_offset(data) + joint * JOINT_SIZE + size_of::<u32>();
+        data[offset..offset + 4].copy_from_slice(&parent.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trip() {
+        let skeleton = skeleton();
+        let clips = vec![clip("walk", 1), clip("run", 2)];
+        let data = write_container(Some(&skeleton), &clips);
+
+        let reader = ContainerReader::new(&data).unwrap();
+        assert_eq!(reader.joint_count(), 3);
+        assert_eq!(reader.clip_names().collect::<Vec<_>>(), ["walk", "run"]);
+        assert_eq!(reader.skeleton().unwrap(), Some(skeleton));
+        assert_eq!(reader.clips().unwrap(), clips);
+        assert_eq!(reader.clip_by_name("run").unwrap().unwrap().end_time(), 0.8);
+    }
+
+    #[test]
+    fn truncated_container_is_an_error() {
+        let data = write_container(Some(&skeleton()), &[clip("walk", 1), clip("run", 2)]);
+        for len in 0..data.len() {
+            assert!(
+                read_all(&data[..len]).is_err(),
+                "prefix of {} bytes was read",
+                len
+            );
+        }
+        assert!(read_all(&data).is_ok());
+    }
+
+    #[test]
+    fn invalid_magic() {
+        let mut data = write_container(Some(&skeleton()), &[]);
+        data[0] = b'X';
+        assert_eq!(read_all(&data).err(), Some(ContainerError::InvalidMagic));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut data = write_container(Some(&skeleton()), &[]);
+        data[4..8].copy_from_slice(&(CONTAINER_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            read_all(&data).err(),
+            Some(ContainerError::UnsupportedVersion(CONTAINER_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn parent_out_of_range() {
+        let mut data = write_container(Some(&skeleton()), &[]);
+        set_parent(&mut data, 2, 7);
+        assert_eq!(
+            read_all(&data).err(),
+            Some(ContainerError::InvalidParent {
+                joint: 2,
+                parent: 7
+            })
+        );
+    }
+
+    #[test]
+    fn parent_cycle() {
+        let mut data = write_container(Some(&skeleton()), &[]);
+        // hips and spine are each other's parent
+        set_parent(&mut data, 1, 2);
+        assert!(matches!(
+            read_all(&data),
+            Err(ContainerError::InvalidParent { .. })
+        ));
+    }
+
+    #[test]
+    fn track_for_missing_joint() {
+        let data = write_container(Some(&skeleton()), &[clip("walk", 5)]);
+        assert_eq!(
+            read_all(&data).err(),
+            Some(ContainerError::InvalidJoint {
+                clip: "walk".to_owned(),
+                joint: 5
+            })
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use super::{clip::Clip, pose::Pose, skeleton::Skeleton};
+
+#[derive(Clone)]
+pub struct CrossFadeTarget {
+    pub pose: Pose,
+    pub clip: Clip,
+    pub time: f32,
+    pub duration: f32,
+    pub elapsed: f32,
+}
+
+/// Plays a clip and fades to new clips over time, multiple fades can be in flight at once
+pub struct CrossFadeController {
+    targets: Vec<CrossFadeTarget>,
+    clip: Option<Clip>,
+    time: f32,
+    pose: Pose,
+    rest_pose: Pose,
+    /// Blended into before it is swapped with `pose`
+    scratch: Pose,
+}
+
+impl CrossFadeController {
+    pub fn new(skeleton: &Skeleton) -> Self {
+        Self {
+            targets: vec![],
+            clip: None,
+            time: 0.0,
+            pose: skeleton.rest_pose.clone(),
+            rest_pose: skeleton.rest_pose.clone(),
+            scratch: skeleton.rest_pose.clone(),
+        }
+    }
+
+    pub fn play(&mut self, clip: Clip) {
+        self.targets.clear();
+        self.time = clip.start_time;
+        self.clip = Some(clip);
+        self.pose = self.rest_pose.clone();
+    }
+
+    pub fn fade_to(&mut self, clip: Clip, duration: f32) {
+        let current_name = match (self.targets.last(), &self.clip) {
+            (Some(target), _) => &target.clip.name,
+            (None, Some(current)) => &current.name,
+            (None, None) => {
+                self.play(clip);
+                return;
+            }
+        };
+        if *current_name == clip.name {
+            return;
+        }
+        self.targets.push(CrossFadeTarget {
+            pose: self.rest_pose.clone(),
+            time: clip.start_time,
+            clip,
+            duration,
+            elapsed: 0.0,
+        });
+    }
+
+    pub fn update(&mut self, delta_time: f32) -> &Pose {
+        if self.clip.is_none() {
+            return &self.pose;
+        }
+
+        // A finished fade fully replaces the current clip and every fade started before it
+        if let Some(finished) = self.targets.iter().rposition(|t| t.elapsed >= t.duration) {
+            let target = self.targets.drain(..=finished).next_back().unwrap();
+            self.time = target.time;
+            self.clip = Some(target.clip);
+        }
+
+        self.pose.clone_from(&self.rest_pose);
+        if let Some(clip) = &self.clip {
+            self.time = clip.sample(&mut self.pose, self.time + delta_time);
+        }
+
+        for target in &mut self.targets {
+            target.time = target
+                .clip
+                .sample(&mut target.pose, target.time + delta_time);
+            target.elapsed += delta_time;
+            let t = if target.duration > 0.0 {
+                (target.elapsed / target.duration).min(1.0)
+            } else {
+                1.0
+            };
+            self.scratch.blend(&self.pose, &target.pose, t, None);
+            std::mem::swap(&mut self.pose, &mut self.scratch);
+        }
+        &self.pose
+    }
+
+    pub fn pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    pub fn current_clip(&self) -> Option<&Clip> {
+        self.clip.as_ref()
+    }
+
+    pub fn targets(&self) -> &[CrossFadeTarget] {
+        &self.targets
+    }
+}
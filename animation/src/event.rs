@@ -0,0 +1,19 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A named marker on a clip's timeline, e.g. a footstep
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClipEvent {
+    pub time: f32,
+    pub name: String,
+}
+
+impl ClipEvent {
+    pub fn new(time: f32, name: &str) -> Self {
+        Self {
+            time,
+            name: name.to_owned(),
+        }
+    }
+}
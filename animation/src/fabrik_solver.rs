@@ -2,6 +2,8 @@ use glam::{Quat, Vec3};
 
 use math::{glam_transform::FromTo, glam_transform::Transform};
 
+use super::ik_solver::{bone_direction, IkSolver, JointConstraint};
+
 #[derive(Debug)]
 pub struct FabrikSolver {
     ik_chain: Vec<Transform>,
@@ -9,6 +11,7 @@ pub struct FabrikSolver {
     pub threshold: f32,
     world_chain: Vec<Vec3>,
     lengths: Vec<f32>,
+    constraints: Vec<Option<(JointConstraint, Quat)>>,
 }
 
 impl FabrikSolver {
@@ -23,70 +26,10 @@ impl FabrikSolver {
             threshold,
             world_chain: vec![],
             lengths: vec![],
+            constraints: vec![],
         }
     }
 
-    pub fn resize(&mut self, len: usize) {
-        self.ik_chain.resize(len, Transform::default());
-        self.world_chain.resize(len, Vec3::default());
-        self.lengths.resize(len, 0.0);
-    }
-
-    pub fn solve(&mut self, target: &Transform) -> bool {
-        if self.len() == 0 {
-            return false;
-        }
-
-        let threshold_squared = self.threshold * self.threshold;
-        self.ik_chain_to_world();
-        let goal = target.translation;
-        let base = self.world_chain[0];
-
-        for _ in 0..self.num_steps {
-            let effector = self.world_chain.last().unwrap();
-            if goal.distance_squared(*effector) < threshold_squared {
-                self.world_to_ik_chain();
-                return true;
-            }
-
-            self.iterate_backward(goal);
-            self.iterate_forward(base);
-
-            // TODO: Match on constraint type
-            /* self.world_to_ik_chain(); */
-            // TODO: Apply constraint
-            /* self.ik_chain_to_world(); */
-        }
-
-        self.world_to_ik_chain();
-        let effector = self.global_transform(self.len() - 1).translation;
-        if goal.distance_squared(effector) < threshold_squared {
-            return true;
-        }
-
-        false
-    }
-
-    pub fn len(&self) -> usize {
-        self.ik_chain.len()
-    }
-
-    pub fn local_transform(&self, index: usize) -> &Transform {
-        &self.ik_chain[index]
-    }
-
-    pub fn set_local_transform(&mut self, index: usize, t: Transform) {
-        self.ik_chain[index] = t;
-    }
-
-    pub fn global_transform(&self, index: usize) -> Transform {
-        let mut world = self.ik_chain[index].clone();
-        for transform in self.ik_chain[0..index].iter().rev() {
-            world = transform.combine(&world);
-        }
-        world.to_owned()
-    }
-
     fn ik_chain_to_world(&mut self) {
         for i in 0..self.len() {
             let world = self.global_transform(i);
@@ -148,31 +91,109 @@ impl FabrikSolver {
         }
     }
 
-    fn apply_ball_and_socket_constraint(&mut self, index: usize, limit: f32) {
-        let parent_rot = if index == 0 {
-            /* self.offset.rotation */
-            Quat::default()
-        } else {
-            self./* world_transform */global_transform(index - 1).rotation
-        };
-        let this_rot = self./* world_transform */global_transform(index).rotation;
-        let parent_dir = parent_rot * Vec3::Z;
-        let this_dir = this_rot * Vec3::Z;
-        let angle = parent_dir.angle_between(this_dir);
-        if angle > f32::to_radians(limit) {
-            let correction = parent_dir.cross(this_dir);
-            let world_space_rotation =
-                parent_rot * Quat::from_axis_angle(correction, f32::to_radians(limit));
-            self./* chain */ik_chain[index].rotation = world_space_rotation * parent_rot.inverse();
+    fn apply_constraints(&mut self) {
+        for index in 0..self.len() {
+            let Some((constraint, rest)) = self.constraints[index] else {
+                continue;
+            };
+            let bone = bone_direction(&self.ik_chain, index);
+            if index == 0 || index + 1 == self.len() {
+                self.ik_chain[index].rotation =
+                    constraint.apply(self.ik_chain[index].rotation, rest, bone);
+                continue;
+            }
+
+            // The parent turns so the next joint stays where the solver put it, this is what
+            // lets the chain swing a hinge around into the plane of the target
+            let parent = self.global_transform(index - 1);
+            let next = self.global_transform(index + 1).translation;
+            self.ik_chain[index].rotation =
+                constraint.apply(self.ik_chain[index].rotation, rest, bone);
+            let moved = self.global_transform(index + 1).translation;
+
+            let inv_rot = parent.rotation.inverse();
+            let delta = Quat::from_to(
+                inv_rot * (moved - parent.translation),
+                inv_rot * (next - parent.translation),
+            );
+            self.ik_chain[index - 1].rotation *= delta;
+        }
+    }
+}
+
+impl IkSolver for FabrikSolver {
+    fn resize(&mut self, len: usize) {
+        self.ik_chain.resize(len, Transform::default());
+        self.world_chain.resize(len, Vec3::default());
+        self.lengths.resize(len, 0.0);
+        self.constraints.resize(len, None);
+    }
+
+    fn len(&self) -> usize {
+        self.ik_chain.len()
+    }
+
+    fn local_transform(&self, index: usize) -> &Transform {
+        &self.ik_chain[index]
+    }
+
+    fn set_local_transform(&mut self, index: usize, t: Transform) {
+        self.ik_chain[index] = t;
+    }
+
+    fn global_transform(&self, index: usize) -> Transform {
+        let mut world = self.ik_chain[index].clone();
+        for transform in self.ik_chain[0..index].iter().rev() {
+            world = transform.combine(&world);
         }
+        world.to_owned()
+    }
+
+    fn set_constraint(&mut self, index: usize, constraint: JointConstraint) {
+        self.constraints[index] = Some((constraint, self.ik_chain[index].rotation));
+    }
+
+    fn remove_constraint(&mut self, index: usize) {
+        self.constraints[index] = None;
     }
 
-    fn apply_hinge_constraint(&mut self, index: usize, axis: Vec3) {
-        let joint = self./* world_transform */global_transform(index);
-        let parent = self./* world_transform */global_transform(index - 1);
-        let current_hinge = joint.rotation * axis;
-        let desired_hinge = parent.rotation * axis;
-        self./* chain */ik_chain[index].rotation =
-            self./* chain */ik_chain[index].rotation * Quat::from_to(current_hinge, desired_hinge);
+    fn constraint(&self, index: usize) -> Option<JointConstraint> {
+        self.constraints[index].map(|(constraint, _)| constraint)
+    }
+
+    fn solve(&mut self, target: &Transform) -> bool {
+        if self.len() == 0 {
+            return false;
+        }
+
+        let threshold_squared = self.threshold * self.threshold;
+        self.ik_chain_to_world();
+        let goal = target.translation;
+        let base = self.world_chain[0];
+
+        for _ in 0..self.num_steps {
+            let effector = self.world_chain.last().unwrap();
+            if goal.distance_squared(*effector) < threshold_squared {
+                self.world_to_ik_chain();
+                return true;
+            }
+
+            self.iterate_backward(goal);
+            self.iterate_forward(base);
+
+            if self.constraints.iter().any(Option::is_some) {
+                self.world_to_ik_chain();
+                self.apply_constraints();
+                self.ik_chain_to_world();
+            }
+        }
+
+        self.world_to_ik_chain();
+        let effector = self.global_transform(self.len() - 1).translation;
+        if goal.distance_squared(effector) < threshold_squared {
+            return true;
+        }
+
+        false
     }
 }
@@ -0,0 +1,245 @@
+use glam::Vec3;
+
+use super::{
+    bake::sample_times, clip::Clip, compression::reduce_keyframes, frame::Frame,
+    interpolation::Interpolation, skeleton::Skeleton, track::ScalarTrack,
+};
+
+/// How foot contacts are found. The thresholds are fractions of how much the height and speed
+/// of the foot change over the clip, so they work whatever the size of the model. A foot that
+/// barely moves is measured against its own length instead and counts as planted
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactSettings {
+    pub sample_rate: f32,
+    /// A foot is low enough when it is within this fraction of its lowest height
+    pub height_threshold: f32,
+    /// A foot is slow enough when it moves at less than this fraction of its top speed
+    pub speed_threshold: f32,
+    /// How fast the ground moves under the character in model space, e.g. backwards at the
+    /// walking speed for a clip animated in place. Without it the speed of the foot while it
+    /// is low is used
+    pub ground_velocity: Option<Vec3>,
+    /// Contacts shorter than this, in seconds, are ignored
+    pub min_contact_time: f32,
+    /// Seconds the pin takes to blend in and out
+    pub blend_time: f32,
+}
+
+impl Default for ContactSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate: 30.0,
+            height_threshold: 0.15,
+            speed_threshold: 0.25,
+            ground_velocity: None,
+            min_contact_time: 0.05,
+            blend_time: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FootContacts {
+    /// How planted the foot is over the normalized time of the clip, like `IkLeg::pin_track`
+    pub pin_track: ScalarTrack,
+    /// Clip times where the foot touches down
+    pub footsteps: Vec<f32>,
+}
+
+impl FootContacts {
+    /// Adds an event named `name` to `clip` for every footstep
+    pub fn add_events(&self, clip: &mut Clip, name: &str) {
+        for &time in &self.footsteps {
+            clip.add_event(time, name);
+        }
+    }
+}
+
+/// Finds when the foot made of `ankle` and `toe` is on the ground in `clip`. The foot is on
+/// the ground while either joint is both low and slow
+pub fn detect_foot_contacts(
+    clip: &Clip,
+    skeleton: &Skeleton,
+    ankle: usize,
+    toe: usize,
+    settings: &ContactSettings,
+) -> FootContacts {
+    let times = sample_times(clip, settings.sample_rate);
+    let mut source = clip.clone();
+    source.looping = false;
+    let mut pose = skeleton.rest_pose.clone();
+    let positions: Vec<(Vec3, Vec3)> = times
+        .iter()
+        .map(|&time| {
+            pose.clone_from(&skeleton.rest_pose);
+            source.sample(&mut pose, time);
+            (
+                pose.global_transform(ankle).translation,
+                pose.global_transform(toe).translation,
+            )
+        })
+        .collect();
+
+    let foot_length =
+        positions.iter().map(|(a, t)| a.distance(*t)).sum::<f32>() / positions.len() as f32;
+    let ankle_contacts = joint_contacts(
+        &times,
+        &positions.iter().map(|p| p.0).collect::<Vec<_>>(),
+        foot_length,
+        settings,
+    );
+    let toe_contacts = joint_contacts(
+        &times,
+        &positions.iter().map(|p| p.1).collect::<Vec<_>>(),
+        foot_length,
+        settings,
+    );
+    let mut contacts: Vec<bool> = ankle_contacts
+        .iter()
+        .zip(&toe_contacts)
+        .map(|(a, t)| *a || *t)
+        .collect();
+    remove_short_contacts(&mut contacts, &times, settings.min_contact_time);
+
+    let footsteps = (0..contacts.len())
+        .filter(|&i| contacts[i] && (i == 0 || !contacts[i - 1]))
+        // A contact at the start of a looping clip continues from its end
+        .filter(|&i| !(i == 0 && clip.looping && contacts[contacts.len() - 1]))
+        .map(|i| times[i])
+        .collect();
+
+    FootContacts {
+        pin_track: pin_track(clip, &times, &contacts, settings.blend_time),
+        footsteps,
+    }
+}
+
+/// How far and fast, in foot lengths, a foot has to move at least to be measured on its own
+const MIN_HEIGHT_RANGE: f32 = 1.0;
+const MIN_SPEED_RANGE: f32 = 10.0;
+
+fn joint_contacts(
+    times: &[f32],
+    positions: &[Vec3],
+    foot_length: f32,
+    settings: &ContactSettings,
+) -> Vec<bool> {
+    // Only sliding along the ground counts, rising and falling is covered by height
+    let velocities: Vec<Vec3> = (0..positions.len())
+        .map(|i| {
+            let (a, b) = (i.saturating_sub(1), (i + 1).min(positions.len() - 1));
+            let dt = times[b] - times[a];
+            if dt <= 0.0 {
+                return Vec3::ZERO;
+            }
+            let velocity = (positions[b] - positions[a]) / dt;
+            Vec3::new(velocity.x, 0.0, velocity.z)
+        })
+        .collect();
+
+    let (min_height, max_height) = positions
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), p| {
+            (min.min(p.y), max.max(p.y))
+        });
+    let height_range = (max_height - min_height).max(foot_length * MIN_HEIGHT_RANGE);
+    let height_limit = min_height + height_range * settings.height_threshold;
+    let low: Vec<bool> = positions.iter().map(|p| p.y <= height_limit).collect();
+
+    let ground_velocity = settings.ground_velocity.unwrap_or_else(|| {
+        let (sum, count) = velocities
+            .iter()
+            .zip(&low)
+            .filter(|(_, low)| **low)
+            .fold((Vec3::ZERO, 0.0), |(sum, count), (v, _)| {
+                (sum + *v, count + 1.0)
+            });
+        if count > 0.0 {
+            sum / count
+        } else {
+            Vec3::ZERO
+        }
+    });
+    let speeds: Vec<f32> = velocities
+        .iter()
+        .map(|v| (*v - Vec3::new(ground_velocity.x, 0.0, ground_velocity.z)).length())
+        .collect();
+    let speed_range = speeds
+        .iter()
+        .copied()
+        .fold(foot_length * MIN_SPEED_RANGE, f32::max);
+    let speed_limit = speed_range * settings.speed_threshold;
+
+    low.iter()
+        .zip(&speeds)
+        .map(|(low, speed)| *low && *speed <= speed_limit)
+        .collect()
+}
+
+fn remove_short_contacts(contacts: &mut [bool], times: &[f32], min_time: f32) {
+    let mut start = None;
+    for i in 0..=contacts.len() {
+        match (start, contacts.get(i).copied().unwrap_or(false)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                if times[i - 1] - times[s] < min_time {
+                    contacts[s..i].fill(false);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Averages the contacts over `blend_time` so the pin ramps in and out, wrapping around for
+/// looping clips
+fn pin_track(clip: &Clip, times: &[f32], contacts: &[bool], blend_time: f32) -> ScalarTrack {
+    let count = contacts.len();
+    let step = match times {
+        [a, b, ..] => b - a,
+        _ => 0.0,
+    };
+    let radius = if step > 0.0 {
+        (blend_time * 0.5 / step).round() as isize
+    } else {
+        0
+    };
+    // The last sample of a looping clip is the same as the first
+    let period = if clip.looping {
+        count.saturating_sub(1).max(1)
+    } else {
+        count
+    };
+
+    let duration = clip.duration();
+    let frames = (0..count)
+        .map(|i| {
+            let window = (-radius..=radius).map(|offset| {
+                let j = i as isize + offset;
+                let j = if clip.looping {
+                    j.rem_euclid(period as isize) as usize
+                } else {
+                    j.clamp(0, count as isize - 1) as usize
+                };
+                if contacts[j] {
+                    1.0
+                } else {
+                    0.0
+                }
+            });
+            let value = window.sum::<f32>() / (2 * radius + 1) as f32;
+            let time = if duration > 0.0 {
+                (times[i] - clip.start_time) / duration
+            } else {
+                0.0
+            };
+            Frame::new_simple(time, value)
+        })
+        .collect();
+
+    reduce_keyframes(
+        &ScalarTrack::new_with_args(Interpolation::Linear, frames),
+        0.001,
+    )
+}
@@ -1,10 +1,21 @@
 use math::quaternion::Quaternion;
 use math::vector3::Vector3;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use super::array_type::ArrayType;
 
 /// Used to store keyframes in a Track
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "A::Slice: Serialize",
+        deserialize = "A::Slice: Deserialize<'de>"
+    ))
+)]
 pub struct Frame<A: ArrayType> {
     pub value: A::Slice,
     pub in_tangent: A::Slice,
@@ -0,0 +1,228 @@
+use glam::{Quat, Vec3};
+
+use math::glam_transform::{FromTo, Transform};
+
+use super::pose::Pose;
+
+/// A joint that is pulled towards a world space target. With a `weight` below 1 it only goes
+/// part of the way from where the animation put it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IkEffector {
+    pub joint: usize,
+    pub target: Vec3,
+    pub weight: f32,
+}
+
+/// FABRIK over a branching hierarchy, e.g. a pelvis with two legs and a spine with two arms,
+/// with any number of effectors solved at the same time
+#[derive(Debug, Clone)]
+pub struct FullBodyIk {
+    pub num_steps: u8,
+    pub threshold: f32,
+    /// Lets the root joint move to help the effectors reach, otherwise it stays in place
+    pub move_root: bool,
+    effectors: Vec<IkEffector>,
+    /// Joints between the root and the effectors, every parent before its children
+    joints: Vec<usize>,
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    lengths: Vec<f32>,
+    positions: Vec<Vec3>,
+}
+
+impl FullBodyIk {
+    /// Returns None if one of `effector_joints` isn't below `root`
+    pub fn new(pose: &Pose, root: usize, effector_joints: &[usize]) -> Option<Self> {
+        let mut joints = vec![root];
+        for &effector in effector_joints {
+            let mut path = vec![effector];
+            let mut current = effector;
+            while current != root {
+                current = pose.parent(current)?;
+                path.push(current);
+            }
+            for joint in path.into_iter().rev() {
+                if !joints.contains(&joint) {
+                    joints.push(joint);
+                }
+            }
+        }
+
+        let parents: Vec<Option<usize>> = joints
+            .iter()
+            .map(|&joint| match joint == root {
+                true => None,
+                false => pose
+                    .parent(joint)
+                    .and_then(|parent| joints.iter().position(|&j| j == parent)),
+            })
+            .collect();
+        let children = (0..joints.len())
+            .map(|node| {
+                (0..joints.len())
+                    .filter(|&child| parents[child] == Some(node))
+                    .collect()
+            })
+            .collect();
+
+        Some(Self {
+            num_steps: 15,
+            threshold: 0.00001,
+            move_root: false,
+            effectors: effector_joints
+                .iter()
+                .map(|&joint| IkEffector {
+                    joint,
+                    target: Vec3::ZERO,
+                    weight: 0.0,
+                })
+                .collect(),
+            lengths: vec![0.0; joints.len()],
+            positions: vec![Vec3::ZERO; joints.len()],
+            joints,
+            parents,
+            children,
+        })
+    }
+
+    pub fn effector(&self, idx: usize) -> &IkEffector {
+        &self.effectors[idx]
+    }
+
+    pub fn effector_count(&self) -> usize {
+        self.effectors.len()
+    }
+
+    pub fn set_target(&mut self, idx: usize, target: Vec3, weight: f32) {
+        let effector = &mut self.effectors[idx];
+        effector.target = target;
+        effector.weight = weight.clamp(0.0, 1.0);
+    }
+
+    /// Poses the joints of `pose` between the root and the effectors, `model` places the pose
+    /// in the world. Returns true if every effector got within the threshold of its goal
+    pub fn solve(&mut self, model: &Transform, pose: &mut Pose) -> bool {
+        self.update_positions(model, pose);
+        for node in 1..self.joints.len() {
+            let parent = self.parents[node].unwrap();
+            self.lengths[node] = (self.positions[node] - self.positions[parent]).length();
+        }
+
+        // Where the effectors should end up, part of the way from the animated pose
+        let goals: Vec<(usize, Vec3)> = self
+            .effectors
+            .iter()
+            .map(|effector| {
+                let node = self.node(effector.joint);
+                let goal = self.positions[node].lerp(effector.target, effector.weight);
+                (node, goal)
+            })
+            .collect();
+
+        let threshold_squared = self.threshold * self.threshold;
+        let reached = |positions: &[Vec3]| {
+            goals
+                .iter()
+                .all(|(node, goal)| positions[*node].distance_squared(*goal) < threshold_squared)
+        };
+
+        for _ in 0..self.num_steps {
+            if reached(&self.positions) {
+                return true;
+            }
+            let base = self.positions[0];
+            self.iterate_backward(&goals);
+            if !self.move_root {
+                self.positions[0] = base;
+            }
+            self.iterate_forward();
+            self.write_pose(model, pose);
+            self.update_positions(model, pose);
+        }
+
+        reached(&self.positions)
+    }
+
+    fn node(&self, joint: usize) -> usize {
+        self.joints.iter().position(|&j| j == joint).unwrap()
+    }
+
+    fn update_positions(&mut self, model: &Transform, pose: &Pose) {
+        for (node, &joint) in self.joints.iter().enumerate() {
+            self.positions[node] = model.combine(&pose.global_transform(joint)).translation;
+        }
+    }
+
+    /// Moves every joint towards its goal and the children it has, a joint with several
+    /// children ends up at the average of where they pull it
+    fn iterate_backward(&mut self, goals: &[(usize, Vec3)]) {
+        for node in (0..self.joints.len()).rev() {
+            let mut sum = Vec3::ZERO;
+            let mut count = 0.0;
+            for &child in &self.children[node] {
+                let direction = (self.positions[node] - self.positions[child]).normalize_or_zero();
+                sum += self.positions[child] + direction * self.lengths[child];
+                count += 1.0;
+            }
+            for (_, goal) in goals.iter().filter(|(n, _)| *n == node) {
+                sum += *goal;
+                count += 1.0;
+            }
+            if count > 0.0 {
+                self.positions[node] = sum / count;
+            }
+        }
+    }
+
+    fn iterate_forward(&mut self) {
+        for node in 1..self.joints.len() {
+            let parent = self.parents[node].unwrap();
+            let direction = (self.positions[node] - self.positions[parent]).normalize_or_zero();
+            self.positions[node] = self.positions[parent] + direction * self.lengths[node];
+        }
+    }
+
+    /// Turns every joint so its children point where the solver put them. A joint with several
+    /// children gets the average of the rotations each of them asks for
+    fn write_pose(&self, model: &Transform, pose: &mut Pose) {
+        for node in 0..self.joints.len() {
+            let joint = self.joints[node];
+            if node == 0 && self.move_root {
+                let parent_world = match pose.parent(joint) {
+                    Some(parent) => model.combine(&pose.global_transform(parent)),
+                    None => model.clone(),
+                };
+                let world = Transform::new(self.positions[0], Quat::IDENTITY, Vec3::ONE);
+                let mut local = pose.local_transform(joint).clone();
+                local.translation = parent_world.inverse().combine(&world).translation;
+                pose.set_local_transform(joint, local);
+            }
+            if self.children[node].is_empty() {
+                continue;
+            }
+
+            let world = model.combine(&pose.global_transform(joint));
+            let inv_rot = world.rotation.inverse();
+            let mut sum = Quat::from_xyzw(0.0, 0.0, 0.0, 0.0);
+            for &child in &self.children[node] {
+                let current = model
+                    .combine(&pose.global_transform(self.joints[child]))
+                    .translation;
+                let delta = Quat::from_to(
+                    inv_rot * (current - world.translation),
+                    inv_rot * (self.positions[child] - world.translation),
+                );
+                // Keeps every rotation in the same hemisphere so they can be averaged
+                sum = sum + if sum.dot(delta) < 0.0 { -delta } else { delta };
+            }
+            // Children pulling in opposite directions cancel out, the joint is left as it is
+            if sum.length_squared() < f32::EPSILON {
+                continue;
+            }
+
+            let mut local = pose.local_transform(joint).clone();
+            local.rotation = (local.rotation * sum.normalize()).normalize();
+            pose.set_local_transform(joint, local);
+        }
+    }
+}
@@ -1,16 +1,42 @@
-use crate::{fabrik_solver::FabrikSolver, pose::Pose, skeleton::Skeleton, track::ScalarTrack};
+use crate::{
+    clip::Clip,
+    fabrik_solver::FabrikSolver,
+    foot_contact::{detect_foot_contacts, ContactSettings},
+    ik_solver::{IkSolver, JointConstraint},
+    pose::Pose,
+    skeleton::Skeleton,
+    track::ScalarTrack,
+    two_bone_ik::TwoBoneIk,
+};
 use glam::{Quat, Vec3};
-use math::glam_transform::Transform;
+use math::glam_transform::{FromTo, Transform};
+
+/// How far the knee may bend from a straight leg, in radians
+const MAX_KNEE_BEND: f32 = 150.0 * std::f32::consts::PI / 180.0;
+/// How much of the leg's length it stretches to before the pelvis has to come down
+const MAX_REACH: f32 = 0.99;
+
+enum LegSolver {
+    Chain(Box<dyn IkSolver>),
+    TwoBone,
+}
 
 pub struct IkLeg {
     pub pin_track: ScalarTrack,
-    solver: FabrikSolver,
+    solver: LegSolver,
     pub ik_pose: Pose,
     pub hip_index: usize,
     pub knee_index: usize,
     pub ankle_index: usize,
     pub toe_index: usize,
     ankle_to_ground_offset: f32,
+    /// The world space direction the knee bends towards with the two bone solver, defaults to
+    /// where the foot points
+    pub pole: Option<Vec3>,
+    /// How far the foot may tilt to follow the ground, in radians
+    pub max_foot_tilt: f32,
+    forward: Vec3,
+    length: f32,
 }
 
 impl IkLeg {
@@ -22,11 +48,80 @@ impl IkLeg {
         ankle_to_ground_offset: f32,
         skeleton: &Skeleton,
     ) -> Self {
-        let solver = {
-            let mut solver = FabrikSolver::new();
+        Self::new_with_solver(
+            hip,
+            knee,
+            ankle,
+            toe,
+            ankle_to_ground_offset,
+            skeleton,
+            FabrikSolver::new(),
+        )
+    }
+
+    pub fn new_with_solver(
+        hip: &str,
+        knee: &str,
+        ankle: &str,
+        toe: &str,
+        ankle_to_ground_offset: f32,
+        skeleton: &Skeleton,
+        solver: impl IkSolver + 'static,
+    ) -> Self {
+        let mut this = Self::new_with_leg_solver(
+            hip,
+            knee,
+            ankle,
+            toe,
+            ankle_to_ground_offset,
+            skeleton,
+            LegSolver::Chain(Box::new(solver)),
+        );
+
+        let rest = &skeleton.rest_pose;
+        let knee_hinge = knee_hinge(
+            rest.global_transform(this.hip_index).translation,
+            &rest.global_transform(this.knee_index),
+            rest.global_transform(this.ankle_index).translation,
+            this.forward,
+        );
+        if let LegSolver::Chain(solver) = &mut this.solver {
             solver.resize(3);
-            solver
-        };
+            solver.set_local_transform(1, rest.local_transform(this.knee_index).clone());
+            solver.set_constraint(1, knee_hinge);
+        }
+        this
+    }
+
+    /// Solves the leg exactly instead of iterating, bending the knee towards `pole`
+    pub fn new_two_bone(
+        hip: &str,
+        knee: &str,
+        ankle: &str,
+        toe: &str,
+        ankle_to_ground_offset: f32,
+        skeleton: &Skeleton,
+    ) -> Self {
+        Self::new_with_leg_solver(
+            hip,
+            knee,
+            ankle,
+            toe,
+            ankle_to_ground_offset,
+            skeleton,
+            LegSolver::TwoBone,
+        )
+    }
+
+    fn new_with_leg_solver(
+        hip: &str,
+        knee: &str,
+        ankle: &str,
+        toe: &str,
+        ankle_to_ground_offset: f32,
+        skeleton: &Skeleton,
+        solver: LegSolver,
+    ) -> Self {
         let mut hip_index: Option<usize> = None;
         let mut knee_index: Option<usize> = None;
         let mut ankle_index: Option<usize> = None;
@@ -43,43 +138,163 @@ impl IkLeg {
                 toe_index = Some(i);
             }
         }
+        let (hip_index, knee_index, ankle_index, toe_index) = (
+            hip_index.unwrap(),
+            knee_index.unwrap(),
+            ankle_index.unwrap(),
+            toe_index.unwrap(),
+        );
+
+        let rest = &skeleton.rest_pose;
+        let hip_position = rest.global_transform(hip_index).translation;
+        let knee_position = rest.global_transform(knee_index).translation;
+        let ankle_position = rest.global_transform(ankle_index).translation;
+        let forward = foot_forward(
+            hip_position,
+            knee_position,
+            ankle_position,
+            rest.global_transform(toe_index).translation,
+        );
+        let length = hip_position.distance(knee_position) + knee_position.distance(ankle_position);
+
         Self {
             pin_track: ScalarTrack::new(),
             solver,
             ik_pose: Pose::new(),
-            hip_index: hip_index.unwrap(),
-            knee_index: knee_index.unwrap(),
-            ankle_index: ankle_index.unwrap(),
-            toe_index: toe_index.unwrap(),
+            hip_index,
+            knee_index,
+            ankle_index,
+            toe_index,
             ankle_to_ground_offset,
+            pole: None,
+            max_foot_tilt: 30.0_f32.to_radians(),
+            forward,
+            length,
         }
     }
 
     pub fn solve(&mut self, model: &Transform, pose: &Pose, ankle_target_position: Vec3) {
-        self.solver
-            .set_local_transform(0, model.combine(&pose.global_transform(self.hip_index)));
-        self.solver
-            .set_local_transform(1, pose.local_transform(self.knee_index).clone());
-        self.solver
-            .set_local_transform(2, pose.local_transform(self.ankle_index).clone());
         self.ik_pose = pose.clone();
-
         let target = Transform::new(
             ankle_target_position + Vec3::Y * self.ankle_to_ground_offset,
             Quat::default(),
             Vec3::ONE,
         );
-        self.solver.solve(&target);
+
+        let solver = match &mut self.solver {
+            LegSolver::Chain(solver) => solver,
+            LegSolver::TwoBone => {
+                let pole = self.pole.unwrap_or(model.rotation * self.forward);
+                TwoBoneIk::new(self.hip_index, self.knee_index, self.ankle_index).solve(
+                    model,
+                    &mut self.ik_pose,
+                    &target,
+                    pole,
+                );
+                return;
+            }
+        };
+
+        solver.set_local_transform(0, model.combine(&pose.global_transform(self.hip_index)));
+        solver.set_local_transform(1, pose.local_transform(self.knee_index).clone());
+        solver.set_local_transform(2, pose.local_transform(self.ankle_index).clone());
+        solver.solve(&target);
 
         let root_world =
             model.combine(&pose.global_transform(pose.parent(self.hip_index).unwrap()));
         self.ik_pose.set_local_transform(
             self.hip_index,
-            root_world.inverse().combine(self.solver.local_transform(0)),
+            root_world.inverse().combine(solver.local_transform(0)),
         );
         self.ik_pose
-            .set_local_transform(self.knee_index, self.solver.local_transform(1).clone());
+            .set_local_transform(self.knee_index, solver.local_transform(1).clone());
         self.ik_pose
-            .set_local_transform(self.ankle_index, self.solver.local_transform(2).clone());
+            .set_local_transform(self.ankle_index, solver.local_transform(2).clone());
+    }
+
+    /// Replaces `pin_track` with one found from where the foot touches the ground in `clip`.
+    /// Returns the clip times of the footsteps
+    pub fn detect_pin_track(
+        &mut self,
+        clip: &Clip,
+        skeleton: &Skeleton,
+        settings: &ContactSettings,
+    ) -> Vec<f32> {
+        let contacts =
+            detect_foot_contacts(clip, skeleton, self.ankle_index, self.toe_index, settings);
+        self.pin_track = contacts.pin_track;
+        contacts.footsteps
+    }
+
+    /// Tilts the foot of `ik_pose` so the sole lies on ground facing `ground_normal`, by
+    /// `weight` which is usually the value of `pin_track`
+    pub fn align_foot(&mut self, model: &Transform, ground_normal: Vec3, weight: f32) {
+        // Animations are made on flat ground so the sole already faces the model's up
+        let up = model.rotation * Vec3::Y;
+        let Some(normal) = ground_normal.try_normalize() else {
+            return;
+        };
+        let (axis, angle) = Quat::from_to(up, normal).to_axis_angle();
+        let angle = angle.min(self.max_foot_tilt) * weight.clamp(0.0, 1.0);
+        if angle <= 0.0 || !axis.is_normalized() {
+            return;
+        }
+
+        let ankle_world = model.combine(&self.ik_pose.global_transform(self.ankle_index));
+        let mut local = self.ik_pose.local_transform(self.ankle_index).clone();
+        let local_axis = ankle_world.rotation.inverse() * axis;
+        local.rotation = (local.rotation * Quat::from_axis_angle(local_axis, angle)).normalize();
+        self.ik_pose.set_local_transform(self.ankle_index, local);
+    }
+
+    /// How far the pelvis has to come down for the ankle to reach `ankle_target_position`,
+    /// zero when the leg reaches it as is
+    pub fn pelvis_offset(
+        &self,
+        model: &Transform,
+        pose: &Pose,
+        ankle_target_position: Vec3,
+    ) -> f32 {
+        let hip = model
+            .combine(&pose.global_transform(self.hip_index))
+            .translation;
+        let target = ankle_target_position + Vec3::Y * self.ankle_to_ground_offset;
+        let to_target = target - hip;
+        let reach = self.length * model.scale.y * MAX_REACH;
+        if to_target.length() <= reach {
+            return 0.0;
+        }
+
+        // Solves |to_target + Y * offset| = reach for the smallest offset
+        let c = to_target.length_squared() - reach * reach;
+        let discriminant = to_target.y * to_target.y - c;
+        if discriminant < 0.0 {
+            // Too far to the side to reach at any height, the closest it gets is level with it
+            return (-to_target.y).max(0.0);
+        }
+        (-to_target.y - discriminant.sqrt()).max(0.0)
+    }
+}
+
+/// Where the foot points, at a right angle to the thigh
+fn foot_forward(hip: Vec3, knee: Vec3, ankle: Vec3, toe: Vec3) -> Vec3 {
+    let thigh = (knee - hip).normalize();
+    let forward = toe - ankle;
+    (forward - thigh * forward.dot(thigh))
+        .try_normalize()
+        .unwrap_or(Vec3::Z)
+}
+
+/// Lets the knee bend only away from where the foot points, starting from a straight leg
+fn knee_hinge(hip: Vec3, knee: &Transform, ankle: Vec3, forward: Vec3) -> JointConstraint {
+    let thigh = (knee.translation - hip).normalize();
+    let shin = (ankle - knee.translation).normalize();
+
+    let axis = thigh.cross(-forward).normalize();
+    let rest_bend = thigh.cross(shin).dot(axis).atan2(thigh.dot(shin));
+    JointConstraint::Hinge {
+        axis: knee.rotation.inverse() * axis,
+        min: -rest_bend,
+        max: MAX_KNEE_BEND - rest_bend,
     }
 }
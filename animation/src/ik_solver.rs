@@ -0,0 +1,115 @@
+use glam::{Quat, Vec3};
+
+use math::glam_transform::Transform;
+
+/// A chain of joints that is posed so its last joint reaches a target. Index 0 is the root of
+/// the chain and its local transform is in the space the target is given in
+pub trait IkSolver {
+    fn resize(&mut self, len: usize);
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn local_transform(&self, index: usize) -> &Transform;
+
+    fn set_local_transform(&mut self, index: usize, t: Transform);
+
+    fn global_transform(&self, index: usize) -> Transform;
+
+    /// Constrains the joint at `index`, the local rotation the joint has now becomes the rest
+    /// rotation the constraint is measured from
+    fn set_constraint(&mut self, index: usize, constraint: JointConstraint);
+
+    fn remove_constraint(&mut self, index: usize);
+
+    fn constraint(&self, index: usize) -> Option<JointConstraint>;
+
+    /// Returns true if the end of the chain got within the threshold of the target
+    fn solve(&mut self, target: &Transform) -> bool;
+
+    /// How far the end of the chain is from `target`
+    fn effector_distance(&self, target: &Transform) -> f32 {
+        if self.is_empty() {
+            return f32::INFINITY;
+        }
+        self.global_transform(self.len() - 1)
+            .translation
+            .distance(target.translation)
+    }
+}
+
+/// Limits on how a joint of an IK chain may rotate. Angles are in radians and measured from the
+/// rest rotation of the joint, see `IkSolver::set_constraint`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JointConstraint {
+    /// `limit` is how far the bone may swing away from its rest direction and `twist_limit` how
+    /// far it may twist around itself
+    BallAndSocket { limit: f32, twist_limit: f32 },
+    /// Only rotates around `axis`, given in the joint's local space
+    Hinge { axis: Vec3, min: f32, max: f32 },
+}
+
+impl JointConstraint {
+    /// Limits the local `rotation` of a joint whose bone points along `bone`
+    pub(crate) fn apply(&self, rotation: Quat, rest: Quat, bone: Vec3) -> Quat {
+        let delta = rest.inverse() * rotation;
+        match *self {
+            JointConstraint::BallAndSocket { limit, twist_limit } => {
+                let (swing, twist) = swing_twist(delta, bone);
+                let swing_angle = swing.angle_between(Quat::IDENTITY);
+                let swing = if swing_angle > limit {
+                    Quat::IDENTITY.slerp(swing, limit / swing_angle)
+                } else {
+                    swing
+                };
+                let twist_angle = twist_angle(twist, bone).clamp(-twist_limit, twist_limit);
+                (rest * swing * Quat::from_axis_angle(bone, twist_angle)).normalize()
+            }
+            JointConstraint::Hinge { axis, min, max } => {
+                let axis = axis.normalize();
+                let (_, twist) = swing_twist(delta, axis);
+                let angle = twist_angle(twist, axis);
+                // A joint bent the wrong way is flipped over instead of straightened, since a
+                // straight chain never bends again
+                let range = min..=max;
+                let angle = if !range.contains(&angle) && range.contains(&-angle) {
+                    -angle
+                } else {
+                    angle.clamp(min, max)
+                };
+                (rest * Quat::from_axis_angle(axis, angle)).normalize()
+            }
+        }
+    }
+}
+
+/// The direction the bone of joint `index` points in its local space, towards the next joint
+pub(crate) fn bone_direction(chain: &[Transform], index: usize) -> Vec3 {
+    chain
+        .get(index + 1)
+        .and_then(|next| next.translation.try_normalize())
+        .unwrap_or(Vec3::Y)
+}
+
+/// Splits `rotation` into a swing followed by a twist around `axis`
+fn swing_twist(rotation: Quat, axis: Vec3) -> (Quat, Quat) {
+    let projected = axis * Vec3::new(rotation.x, rotation.y, rotation.z).dot(axis);
+    let twist = Quat::from_xyzw(projected.x, projected.y, projected.z, rotation.w);
+    let twist = if twist.length_squared() < f32::EPSILON {
+        Quat::IDENTITY
+    } else {
+        twist.normalize()
+    };
+    (rotation * twist.conjugate(), twist)
+}
+
+/// Signed angle of a twist around `axis`, between -PI and PI
+fn twist_angle(twist: Quat, axis: Vec3) -> f32 {
+    let twist = if twist.w < 0.0 { -twist } else { twist };
+    2.0 * Vec3::new(twist.x, twist.y, twist.z)
+        .dot(axis)
+        .atan2(twist.w)
+}
@@ -0,0 +1,177 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Any of the serializable animation types, e.g. a `Clip` or a `Skeleton`, as JSON
+pub fn to_json<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(value)
+}
+
+pub fn from_json<T: DeserializeOwned>(json: &str) -> serde_json::Result<T> {
+    serde_json::from_str(json)
+}
+
+pub fn save_json<T: Serialize>(value: &T, path: impl AsRef<Path>) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, value)?;
+    Ok(())
+}
+
+pub fn load_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> io::Result<T> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use std::fmt::Debug;
+
+    use glam::{Quat, Vec3};
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use math::glam_transform::Transform;
+
+    use super::{from_json, to_json};
+    use crate::{
+        clip::Clip,
+        frame::Frame,
+        interpolation::Interpolation,
+        pose::Pose,
+        skeleton::Skeleton,
+        track::{QuatTrack, ScalarTrack, Vector3Track},
+        transform_track::TransformTrack,
+        weights_track::WeightsTrack,
+    };
+
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: &T) {
+        let json = to_json(value).unwrap();
+        let result: T = from_json(&json).unwrap();
+        assert_eq!(*value, result);
+    }
+
+    fn position_track() -> Vector3Track {
+        Vector3Track::new_with_args(
+            Interpolation::Cubic,
+            vec![
+                Frame::new(
+                    0.0,
+                    Vec3::ZERO,
+                    Vec3::new(0.5, 0.0, -1.0),
+                    Vec3::new(0.0, 1.0, 0.0),
+                ),
+                Frame::new(0.7, Vec3::X, Vec3::Y, Vec3::new(0.3, 1.1, -0.2)),
+                Frame::new(1.3, Vec3::Z, Vec3::ZERO, Vec3::new(0.6, 1.0, -0.4)),
+            ],
+        )
+    }
+
+    fn rotation_track() -> QuatTrack {
+        QuatTrack::new_with_args(
+            Interpolation::Linear,
+            vec![
+                Frame::new_simple(0.0, Quat::IDENTITY),
+                Frame::new_simple(0.5, Quat::from_rotation_y(0.8)),
+                Frame::new_simple(1.3, Quat::from_euler(glam::EulerRot::XYZ, 0.1, 0.2, 0.3)),
+            ],
+        )
+    }
+
+    fn pose() -> Pose {
+        let mut pose = Pose::new();
+        let transforms = [
+            Transform::new(Vec3::new(0.0, 1.0, 0.0), Quat::IDENTITY, Vec3::ONE),
+            Transform::new(
+                Vec3::new(0.1, 0.4, 0.0),
+                Quat::from_rotation_z(0.3),
+                Vec3::ONE,
+            ),
+            Transform::new(
+                Vec3::new(0.0, 0.4, 0.05),
+                Quat::from_rotation_x(-0.2),
+                Vec3::splat(1.5),
+            ),
+        ];
+        for (i, transform) in transforms.into_iter().enumerate() {
+            pose.add_local_transform(transform);
+            pose.add_parent(i.checked_sub(1));
+        }
+        pose
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        round_trip(&Frame::new(
+            0.25,
+            Quat::from_rotation_x(0.1),
+            Quat::from_rotation_y(-0.4),
+            Quat::from_rotation_z(1.2),
+        ));
+    }
+
+    #[test]
+    fn track_round_trip() {
+        round_trip(&position_track());
+        round_trip(&rotation_track());
+    }
+
+    #[test]
+    fn transform_track_round_trip() {
+        let mut track = TransformTrack::new(2);
+        track.position = position_track();
+        track.rotation = rotation_track();
+        round_trip(&track);
+    }
+
+    #[test]
+    fn pose_round_trip() {
+        round_trip(&pose());
+    }
+
+    #[test]
+    fn skeleton_round_trip() {
+        let rest_pose = pose();
+        let mut bind_pose = pose();
+        let mut root = bind_pose.local_transform(0).clone();
+        root.rotation = Quat::from_rotation_y(0.5);
+        bind_pose.set_local_transform(0, root);
+        let names = vec!["hips".to_string(), "spine".to_string(), "head".to_string()];
+        let skeleton = Skeleton::new(rest_pose, bind_pose, names);
+
+        // Only the poses and names are stored, the inverse bind pose is rebuilt
+        let json = to_json(&skeleton).unwrap();
+        assert!(!json.contains("inverse_bind_pose"));
+        let result: Skeleton = from_json(&json).unwrap();
+        assert_eq!(skeleton, result);
+    }
+
+    #[test]
+    fn clip_round_trip() {
+        let mut clip = Clip::new(Some("walk"));
+        let mut hips = TransformTrack::new(0);
+        hips.position = position_track();
+        hips.rotation = rotation_track();
+        clip.add_track(hips);
+        let mut spine = TransformTrack::new(1);
+        spine.rotation = rotation_track();
+        clip.add_track(spine);
+        let mut head = TransformTrack::new(2);
+        head.scale = position_track();
+        clip.add_track(head);
+        let mut weights = WeightsTrack::new(3);
+        weights.weights = vec![ScalarTrack::new_with_args(
+            Interpolation::Constant,
+            vec![Frame::new_simple(0.0, 0.0), Frame::new_simple(1.0, 0.75)],
+        )];
+        clip.add_weights_track(weights);
+        clip.add_event(0.4, "footstep");
+        clip.looping = false;
+        clip.recalculate_duration();
+        clip.extract_root_motion(0, Vec3::Y, true);
+
+        round_trip(&clip);
+    }
+}
@@ -1,11 +1,33 @@
+pub mod additive;
 pub mod array_type;
+pub mod bake;
+pub mod blend_space;
+pub mod bone_mask;
+pub mod ccd_solver;
 pub mod clip;
+pub mod compression;
+pub mod container;
+pub mod cross_fade;
+pub mod event;
 pub mod fabrik_solver;
+pub mod foot_contact;
 pub mod frame;
+pub mod full_body_ik;
 pub mod ik_leg;
+pub mod ik_solver;
 pub mod interpolation;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod look_at;
+pub mod mirror;
 pub mod pose;
+pub mod retarget;
+pub mod root_motion;
+pub mod secondary_motion;
 pub mod skeleton;
+pub mod state_machine;
 pub mod track;
 pub mod track_helpers;
 pub mod transform_track;
+pub mod two_bone_ik;
+pub mod weights_track;
@@ -0,0 +1,154 @@
+use glam::{Quat, Vec3};
+
+use math::glam_transform::{FromTo, LookRotation, Transform};
+
+use super::pose::Pose;
+
+/// A joint of a look at chain. `weight` is its share of the rotation relative to the other
+/// joints and `limit` the most it may turn, in radians
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AimJoint {
+    pub joint: usize,
+    pub weight: f32,
+    pub limit: f32,
+}
+
+impl AimJoint {
+    pub fn new(joint: usize, weight: f32, limit: f32) -> Self {
+        Self {
+            joint,
+            weight,
+            limit,
+        }
+    }
+}
+
+/// Turns a chain of joints, e.g. the spine up to the head, so the last one aims at a target
+#[derive(Debug, Clone)]
+pub struct LookAt {
+    /// Ordered from the top of the hierarchy down to the joint that aims
+    pub chain: Vec<AimJoint>,
+    /// The axis that should point at the target, in the local space of the last joint
+    pub forward: Vec3,
+    /// A local axis of the last joint that is kept pointing up, otherwise the chain turns as
+    /// little as possible and keeps the roll of the animation
+    pub up: Option<Vec3>,
+    /// Seconds it takes to blend fully in or out
+    pub blend_time: f32,
+    target: Option<Vec3>,
+    active: bool,
+    blend: f32,
+}
+
+impl LookAt {
+    pub fn new(chain: Vec<AimJoint>, forward: Vec3) -> Self {
+        Self {
+            chain,
+            forward,
+            up: None,
+            blend_time: 0.25,
+            target: None,
+            active: false,
+            blend: 0.0,
+        }
+    }
+
+    /// A chain from `from` down to `to` where every joint takes an equal share and may turn
+    /// up to `limit`. Returns None if `to` isn't below `from`
+    pub fn from_chain(
+        pose: &Pose,
+        from: usize,
+        to: usize,
+        forward: Vec3,
+        limit: f32,
+    ) -> Option<Self> {
+        let mut joints = vec![to];
+        let mut current = to;
+        while current != from {
+            current = pose.parent(current)?;
+            joints.push(current);
+        }
+        let chain = joints
+            .into_iter()
+            .rev()
+            .map(|joint| AimJoint::new(joint, 1.0, limit))
+            .collect();
+        Some(Self::new(chain, forward))
+    }
+
+    /// Starts blending towards looking at `target`, a position in world space
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = Some(target);
+        self.active = true;
+    }
+
+    /// Blends out while still looking at the last target
+    pub fn clear_target(&mut self) {
+        self.active = false;
+    }
+
+    pub fn blend(&self) -> f32 {
+        self.blend
+    }
+
+    /// Advances the blend and turns the chain of `pose`, which `model` places in the world
+    pub fn apply(&mut self, model: &Transform, pose: &mut Pose, delta_time: f32) {
+        let step = if self.blend_time > 0.0 {
+            delta_time / self.blend_time
+        } else {
+            1.0
+        };
+        self.blend = if self.active {
+            (self.blend + step).min(1.0)
+        } else {
+            (self.blend - step).max(0.0)
+        };
+
+        let (Some(target), Some(aim)) = (self.target, self.chain.last()) else {
+            return;
+        };
+        if self.blend <= 0.0 {
+            return;
+        }
+        let blend = self.blend * self.blend * (3.0 - 2.0 * self.blend);
+        // The part that isn't blended in is left over as if a joint after the chain took it
+        let total_weight: f32 = self.chain.iter().map(|j| j.weight).sum();
+        let left_over = total_weight * (1.0 - blend) / blend;
+
+        for (i, aim_joint) in self.chain.iter().enumerate() {
+            let remaining: f32 = self.chain[i..].iter().map(|j| j.weight).sum::<f32>() + left_over;
+            if remaining <= 0.0 {
+                break;
+            }
+
+            let aim_world = model.combine(&pose.global_transform(aim.joint));
+            let Some(direction) = (target - aim_world.translation).try_normalize() else {
+                return;
+            };
+            let total = match self.up {
+                Some(up) => {
+                    Quat::look_rotation(direction, Vec3::Y)
+                        * Quat::look_rotation(self.forward, up).inverse()
+                        * aim_world.rotation.inverse()
+                }
+                None => Quat::from_to(aim_world.rotation * self.forward, direction),
+            };
+            // Takes the short way around
+            let total = if total.w < 0.0 { -total } else { total };
+
+            let share = Quat::IDENTITY.slerp(total, aim_joint.weight / remaining);
+            let (axis, angle) = share.to_axis_angle();
+            let angle = angle.min(aim_joint.limit);
+            if angle <= 0.0 {
+                continue;
+            }
+
+            let joint_world = model.combine(&pose.global_transform(aim_joint.joint));
+            let mut local = pose.local_transform(aim_joint.joint).clone();
+            let local_axis = joint_world.rotation.inverse() * axis;
+            local.rotation =
+                (local.rotation * Quat::from_axis_angle(local_axis, angle)).normalize();
+            pose.set_local_transform(aim_joint.joint, local);
+        }
+    }
+}
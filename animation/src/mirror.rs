@@ -0,0 +1,173 @@
+use glam::{Quat, Vec3};
+
+use super::{clip::Clip, pose::Pose, skeleton::Skeleton, transform_track::TransformTrack};
+
+/// Name fragments of paired joints, tried in order
+pub const DEFAULT_JOINT_PAIRS: [(&str, &str); 6] = [
+    ("Left", "Right"),
+    ("left", "right"),
+    (".L", ".R"),
+    (".l", ".r"),
+    ("_L", "_R"),
+    ("_l", "_r"),
+];
+
+/// The plane animations are mirrored across, `YZ` swaps left and right of a character facing Z
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MirrorPlane {
+    #[default]
+    YZ,
+    XZ,
+    XY,
+}
+
+impl MirrorPlane {
+    fn normal_axis(&self) -> usize {
+        match self {
+            MirrorPlane::YZ => 0,
+            MirrorPlane::XZ => 1,
+            MirrorPlane::XY => 2,
+        }
+    }
+
+    pub fn reflect_vector(&self, v: Vec3) -> Vec3 {
+        let mut v = v;
+        v[self.normal_axis()] *= -1.0;
+        v
+    }
+
+    /// A reflected rotation turns around the reflected axis the other way, which keeps the
+    /// component along the normal and flips the other two
+    pub fn reflect_rotation(&self, q: Quat) -> Quat {
+        let mut v = -Vec3::new(q.x, q.y, q.z);
+        v[self.normal_axis()] *= -1.0;
+        Quat::from_xyzw(v.x, v.y, v.z, q.w)
+    }
+}
+
+/// Mirrors poses and clips of a skeleton, swapping the animation of paired joints.
+/// The bind pose is expected to be symmetric, differences in how the joints of a pair are
+/// oriented in it are compensated for
+#[derive(Debug, Clone)]
+pub struct Mirror {
+    pub plane: MirrorPlane,
+    counterparts: Vec<usize>,
+    parent_corrections: Vec<Quat>,
+    bind_corrections: Vec<Quat>,
+}
+
+impl Mirror {
+    pub fn new(skeleton: &Skeleton, plane: MirrorPlane, pairs: &[(&str, &str)]) -> Self {
+        let joint_count = skeleton.bind_pose.len();
+        let counterparts: Vec<usize> = (0..joint_count)
+            .map(|joint| {
+                let name = skeleton.joint_name(joint);
+                pairs
+                    .iter()
+                    .flat_map(|(left, right)| [(left, right), (right, left)])
+                    .filter(|(from, _)| name.contains(*from))
+                    .find_map(|(from, to)| skeleton.joint_index(&name.replace(from, to)))
+                    .unwrap_or(joint)
+            })
+            .collect();
+
+        let bind_pose = &skeleton.bind_pose;
+        let bind_rotation = |joint: Option<usize>| match joint {
+            Some(joint) => bind_pose.global_transform(joint).rotation,
+            None => Quat::IDENTITY,
+        };
+        let mut parent_corrections = vec![];
+        let mut bind_corrections = vec![];
+        for (joint, &source) in counterparts.iter().enumerate() {
+            parent_corrections.push(
+                bind_rotation(bind_pose.parent(joint)).inverse()
+                    * plane.reflect_rotation(bind_rotation(bind_pose.parent(source))),
+            );
+            bind_corrections.push(
+                plane
+                    .reflect_rotation(bind_rotation(Some(source)))
+                    .inverse()
+                    * bind_rotation(Some(joint)),
+            );
+        }
+
+        Self {
+            plane,
+            counterparts,
+            parent_corrections,
+            bind_corrections,
+        }
+    }
+
+    pub fn counterpart(&self, joint: usize) -> usize {
+        self.counterparts[joint]
+    }
+
+    pub fn mirror_pose(&self, pose: &Pose, out_pose: &mut Pose) {
+        for joint in 0..self.counterparts.len() {
+            let source = pose.local_transform(self.counterparts[joint]);
+            let mut local = source.clone();
+            local.translation = self.mirror_translation(joint, source.translation);
+            local.rotation = self.mirror_rotation(joint, source.rotation).normalize();
+            out_pose.set_local_transform(joint, local);
+        }
+    }
+
+    /// Mirrors every keyframe and moves it to the track of the paired joint.
+    /// See [`Clip::extract_root_motion`] about root motion
+    pub fn mirror_clip(&self, clip: &Clip) -> Clip {
+        let mut mirrored = Clip::new(Some(&format!("{} (mirrored)", clip.name)));
+        mirrored.looping = clip.looping;
+        for track in clip.tracks() {
+            let joint = self.counterparts[track.id as usize];
+            let mut result = TransformTrack::new(joint as u32);
+            result.position = track.position.clone();
+            for frame in &mut result.position.frames {
+                for value in [
+                    &mut frame.value,
+                    &mut frame.in_tangent,
+                    &mut frame.out_tangent,
+                ] {
+                    *value = self
+                        .mirror_translation(joint, Vec3::from_array(*value))
+                        .to_array();
+                }
+            }
+            result.rotation = track.rotation.clone();
+            for frame in &mut result.rotation.frames {
+                for value in [
+                    &mut frame.value,
+                    &mut frame.in_tangent,
+                    &mut frame.out_tangent,
+                ] {
+                    *value = self
+                        .mirror_rotation(joint, Quat::from_array(*value))
+                        .to_array();
+                }
+            }
+            result.scale = track.scale.clone();
+            mirrored.add_track(result);
+        }
+        for track in clip.weights_tracks() {
+            mirrored.add_weights_track(track.clone());
+        }
+        for event in clip.events() {
+            mirrored.add_event(event.time, &event.name);
+        }
+        mirrored.recalculate_duration();
+        mirrored
+    }
+
+    /// Local translation of `joint` when mirroring its counterpart. Linear so it also works on
+    /// tangents
+    fn mirror_translation(&self, joint: usize, translation: Vec3) -> Vec3 {
+        self.parent_corrections[joint] * self.plane.reflect_vector(translation)
+    }
+
+    /// Local rotation of `joint` when mirroring its counterpart, also linear in `rotation`
+    fn mirror_rotation(&self, joint: usize, rotation: Quat) -> Quat {
+        self.parent_corrections[joint]
+            * self.plane.reflect_rotation(rotation)
+            * self.bind_corrections[joint]
+    }
+}
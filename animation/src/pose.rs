@@ -1,10 +1,14 @@
-use glam::Mat4;
+use glam::{Mat4, Quat};
 
-use math::glam_transform::Transform;
+use math::{dual_quat::DualQuat, glam_transform::Transform};
 
-use super::{clip::Clip, skeleton::Skeleton};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{additive::AdditiveReference, bone_mask::BoneMask, clip::Clip, skeleton::Skeleton};
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pose {
     joints: Vec<Transform>,
     parents: Vec<Option<usize>>,
@@ -18,10 +22,9 @@ impl Pose {
         }
     }
 
+    /// The first frame of `clip`, see `AdditiveReference` for other reference poses
     pub fn make_additive(skeleton: &Skeleton, clip: &Clip) -> Self {
-        let mut result = skeleton.rest_pose.clone();
-        clip.sample(&mut result, clip.start_time);
-        result
+        AdditiveReference::FirstFrame.pose(skeleton, clip)
     }
 
     pub fn len(&self) -> usize {
@@ -76,6 +79,17 @@ impl Pose {
         result
     }
 
+    /// Global transforms as dual quaternions, scale is dropped
+    pub fn dual_quat_palette(&self) -> Vec<DualQuat> {
+        (0..self.len())
+            .map(|i| DualQuat::from(&self.global_transform(i)))
+            .collect()
+    }
+
+    pub fn parents(&self) -> &[Option<usize>] {
+        &self.parents
+    }
+
     pub fn parent(&self, idx: usize) -> Option<usize> {
         self.parents[idx]
     }
@@ -110,6 +124,18 @@ impl Pose {
         add_pose: &Pose,
         base_pose: &Pose,
         blend_root: Option<usize>,
+    ) {
+        self.add_weighted(in_pose, add_pose, base_pose, 1.0, blend_root);
+    }
+
+    /// Like `add` but only applies `weight` of the difference between `add_pose` and `base_pose`
+    pub fn add_weighted(
+        &mut self,
+        in_pose: &Pose,
+        add_pose: &Pose,
+        base_pose: &Pose,
+        weight: f32,
+        blend_root: Option<usize>,
     ) {
         for i in 0..add_pose.len() {
             if let Some(blend_root) = blend_root {
@@ -117,19 +143,44 @@ impl Pose {
                     continue;
                 }
             }
-            let input = in_pose.local_transform(i);
-            let additive = add_pose.local_transform(i);
-            let additive_base = base_pose.local_transform(i);
-            let result = Transform {
-                translation: input.translation + (additive.translation - additive_base.translation),
-                rotation: (input.rotation * (additive_base.rotation.inverse() * additive.rotation)),
-                scale: input.scale + (additive.scale - additive_base.scale),
-            };
+            let result = additive_transform(
+                in_pose.local_transform(i),
+                add_pose.local_transform(i),
+                base_pose.local_transform(i),
+                weight,
+            );
+            self.set_local_transform(i, result);
+        }
+    }
+
+    /// Like `blend` but `t` is scaled by the weight `mask` gives each joint
+    pub fn blend_masked(&mut self, a: &Self, b: &Self, t: f32, mask: &BoneMask) {
+        for i in 0..self.len() {
+            let t = t * mask.weight(i);
+            self.set_local_transform(i, a.local_transform(i).mix(b.local_transform(i), t));
+        }
+    }
+
+    /// Like `add` but only the share of the additive pose `mask` gives each joint is applied
+    pub fn add_masked(
+        &mut self,
+        in_pose: &Pose,
+        add_pose: &Pose,
+        base_pose: &Pose,
+        mask: &BoneMask,
+    ) {
+        for i in 0..add_pose.len() {
+            let result = additive_transform(
+                in_pose.local_transform(i),
+                add_pose.local_transform(i),
+                base_pose.local_transform(i),
+                mask.weight(i),
+            );
             self.set_local_transform(i, result);
         }
     }
 
-    fn is_in_hierarchy(&self, parent: usize, search: usize) -> bool {
+    pub(crate) fn is_in_hierarchy(&self, parent: usize, search: usize) -> bool {
         if search == parent {
             return true;
         }
@@ -143,3 +194,19 @@ impl Pose {
         false
     }
 }
+
+fn additive_transform(
+    input: &Transform,
+    additive: &Transform,
+    additive_base: &Transform,
+    weight: f32,
+) -> Transform {
+    let rotation =
+        Quat::IDENTITY.lerp(additive_base.rotation.inverse() * additive.rotation, weight);
+    Transform {
+        translation: input.translation
+            + (additive.translation - additive_base.translation) * weight,
+        rotation: (input.rotation * rotation.normalize()),
+        scale: input.scale + (additive.scale - additive_base.scale) * weight,
+    }
+}
@@ -0,0 +1,378 @@
+use glam::{Quat, Vec3};
+
+use math::glam_transform::Transform;
+
+use super::{
+    bake::{clip_from_poses, sample_poses, sample_times},
+    clip::Clip,
+    pose::Pose,
+    skeleton::Skeleton,
+};
+
+/// Plays poses and clips authored for one skeleton on another.
+/// Both skeletons are expected to share roughly the same bind pose (e.g. both in a T-pose),
+/// the difference between the joint orientations of the two bind poses is compensated for
+#[derive(Clone)]
+pub struct Retargeter {
+    source: Skeleton,
+    target: Skeleton,
+    /// The source joint driving each target joint
+    mapping: Vec<Option<usize>>,
+    /// Target joints ordered so parents come before their children
+    order: Vec<usize>,
+    source_bind: Vec<Transform>,
+    target_bind: Vec<Transform>,
+    /// A target joint below the root that carries translation too, e.g. the hips under a
+    /// static root joint
+    hips: Option<usize>,
+    /// Scales the root translation to make up for differently sized skeletons, measured from
+    /// the mapping unless set by hand
+    translation_scale: Option<f32>,
+}
+
+impl Retargeter {
+    /// Maps joints with the same name, ignoring case and namespaces like "mixamorig:"
+    pub fn new(source: &Skeleton, target: &Skeleton) -> Self {
+        let source_names: Vec<String> = (0..source.rest_pose.len())
+            .map(|i| normalize_name(source.joint_name(i)))
+            .collect();
+        let mapping = (0..target.rest_pose.len())
+            .map(|i| {
+                let name = normalize_name(target.joint_name(i));
+                source_names.iter().position(|n| *n == name)
+            })
+            .collect();
+
+        let mut order: Vec<usize> = (0..target.rest_pose.len()).collect();
+        order.sort_by_key(|&i| depth(&target.rest_pose, i));
+
+        Self {
+            source_bind: global_transforms(&source.bind_pose),
+            target_bind: global_transforms(&target.bind_pose),
+            source: source.clone(),
+            target: target.clone(),
+            mapping,
+            order,
+            hips: None,
+            translation_scale: None,
+        }
+    }
+
+    /// The set scale, otherwise the ratio between the heights above the origin of the joint
+    /// carrying the translation in the two bind poses, along Y which is up in glTF
+    pub fn translation_scale(&self) -> f32 {
+        self.translation_scale
+            .unwrap_or_else(|| self.measured_scale(self.hips.or_else(|| self.top_root())))
+    }
+
+    pub fn set_translation_scale(&mut self, scale: f32) {
+        self.translation_scale = Some(scale);
+    }
+
+    /// Carries the animated translation of `target_joint` over as well as that of the root,
+    /// e.g. for the hips below a static root joint. Without it `retarget_clip` picks the first
+    /// joint the clip moves
+    pub fn set_hips(&mut self, target_joint: &str) -> bool {
+        match self.target.joint_index(target_joint) {
+            Some(joint) => {
+                self.hips = Some(joint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Overrides the name based mapping of `target_joint`
+    pub fn map_joint(&mut self, target_joint: &str, source_joint: &str) -> bool {
+        match (
+            self.target.joint_index(target_joint),
+            self.source.joint_index(source_joint),
+        ) {
+            (Some(target), Some(source)) => {
+                self.mapping[target] = Some(source);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Leaves `target_joint` in its rest pose
+    pub fn unmap_joint(&mut self, target_joint: &str) -> bool {
+        match self.target.joint_index(target_joint) {
+            Some(target) => {
+                self.mapping[target] = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn source_joint(&self, target_joint: usize) -> Option<usize> {
+        self.mapping[target_joint]
+    }
+
+    /// Writes `source_pose` into `out_pose`, which has to be laid out like the target skeleton.
+    /// Only rotations and the translation of the root joints and the joint set with `set_hips`
+    /// are carried over so the target keeps its own limb lengths
+    pub fn retarget_pose(&self, source_pose: &Pose, out_pose: &mut Pose) {
+        self.retarget_pose_with_hips(source_pose, out_pose, self.hips);
+    }
+
+    fn retarget_pose_with_hips(
+        &self,
+        source_pose: &Pose,
+        out_pose: &mut Pose,
+        hips: Option<usize>,
+    ) {
+        let translation_scale = self
+            .translation_scale
+            .unwrap_or_else(|| self.measured_scale(hips.or_else(|| self.top_root())));
+        let source_globals = global_transforms(source_pose);
+        let rest_pose = &self.target.rest_pose;
+        let mut out_globals = vec![Transform::default(); rest_pose.len()];
+
+        for &joint in &self.order {
+            let rest = rest_pose.local_transform(joint);
+            let parent_global = match rest_pose.parent(joint) {
+                Some(parent) => out_globals[parent].clone(),
+                None => Transform::default(),
+            };
+            let local = match self.mapping[joint] {
+                Some(source) => {
+                    let source_global = &source_globals[source];
+                    let source_bind = &self.source_bind[source];
+                    let target_bind = &self.target_bind[joint];
+
+                    // Apply the rotation the source joint made away from its bind pose
+                    let delta = source_global.rotation * source_bind.rotation.inverse();
+                    let rotation = parent_global.rotation.inverse() * delta * target_bind.rotation;
+                    let translation = if self.is_root(joint) || hips == Some(joint) {
+                        let offset = (source_global.translation - source_bind.translation)
+                            * translation_scale;
+                        parent_global
+                            .inverse()
+                            .combine(&Transform::new(
+                                target_bind.translation + offset,
+                                Quat::IDENTITY,
+                                Vec3::ONE,
+                            ))
+                            .translation
+                    } else {
+                        rest.translation
+                    };
+                    Transform::new(translation, rotation.normalize(), rest.scale)
+                }
+                None => rest.clone(),
+            };
+            out_globals[joint] = parent_global.combine(&local);
+            out_pose.set_local_transform(joint, local);
+        }
+    }
+
+    /// Samples `clip` on the source skeleton `sample_rate` times a second and bakes the result
+    /// into a clip for the target skeleton. Morph target weights aren't carried over
+    pub fn retarget_clip(&self, clip: &Clip, sample_rate: f32) -> Clip {
+        let times = sample_times(clip, sample_rate);
+        let hips = self.hips.or_else(|| self.moving_joint(clip));
+        let mut target_pose = self.target.rest_pose.clone();
+        let poses = sample_poses(clip, &self.source.rest_pose, &times, |source_pose, _| {
+            self.retarget_pose_with_hips(source_pose, &mut target_pose, hips);
+            target_pose.clone()
+        });
+
+        let mapped = (0..self.mapping.len()).filter(|j| self.mapping[*j].is_some());
+        clip_from_poses(clip, mapped, &times, &poses)
+    }
+
+    /// A mapped joint without mapped ancestors, these carry the translation of the character
+    fn is_root(&self, joint: usize) -> bool {
+        let mut parent = self.target.rest_pose.parent(joint);
+        while let Some(p) = parent {
+            if self.mapping[p].is_some() {
+                return false;
+            }
+            parent = self.target.rest_pose.parent(p);
+        }
+        true
+    }
+
+    fn top_root(&self) -> Option<usize> {
+        (0..self.mapping.len()).find(|j| self.mapping[*j].is_some() && self.is_root(*j))
+    }
+
+    /// The topmost mapped joint whose source joint has its translation animated by `clip`
+    fn moving_joint(&self, clip: &Clip) -> Option<usize> {
+        self.order.iter().copied().find(|&joint| {
+            let Some(source) = self.mapping[joint] else {
+                return false;
+            };
+            clip.tracks()
+                .iter()
+                .filter(|track| track.id as usize == source)
+                .any(|track| {
+                    let frames = &track.position.frames;
+                    frames.iter().any(|frame| frame.value != frames[0].value)
+                })
+        })
+    }
+
+    fn measured_scale(&self, joint: Option<usize>) -> f32 {
+        joint
+            .and_then(|target| {
+                let source = self.mapping[target]?;
+                let source_height = self.source_bind[source].translation.y.abs();
+                let target_height = self.target_bind[target].translation.y.abs();
+                (source_height > f32::EPSILON).then(|| target_height / source_height)
+            })
+            .unwrap_or(1.0)
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.rsplit(':').next().unwrap_or(name).to_lowercase()
+}
+
+fn depth(pose: &Pose, joint: usize) -> usize {
+    let mut depth = 0;
+    let mut parent = pose.parent(joint);
+    while let Some(p) = parent {
+        depth += 1;
+        parent = pose.parent(p);
+    }
+    depth
+}
+
+fn global_transforms(pose: &Pose) -> Vec<Transform> {
+    (0..pose.len()).map(|i| pose.global_transform(i)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use math::glam_transform::Transform;
+
+    use super::Retargeter;
+    use crate::{
+        clip::Clip, frame::Frame, interpolation::Interpolation, pose::Pose, skeleton::Skeleton,
+        track::Vector3Track, transform_track::TransformTrack,
+    };
+
+    /// A static root with the hips, a spine and two legs of three joints below it
+    fn skeleton() -> Skeleton {
+        let joints: [(&str, Option<usize>, Vec3); 10] = [
+            ("root", None, Vec3::ZERO),
+            ("hips", Some(0), Vec3::new(0.0, 1.0, 0.0)),
+            ("spine", Some(1), Vec3::new(0.0, 0.3, 0.0)),
+            ("head", Some(2), Vec3::new(0.0, 0.4, 0.0)),
+            ("left_thigh", Some(1), Vec3::new(0.1, -0.05, 0.0)),
+            ("left_shin", Some(4), Vec3::new(0.0, -0.45, 0.0)),
+            ("left_foot", Some(5), Vec3::new(0.0, -0.45, 0.0)),
+            ("right_thigh", Some(1), Vec3::new(-0.1, -0.05, 0.0)),
+            ("right_shin", Some(7), Vec3::new(0.0, -0.45, 0.0)),
+            ("right_foot", Some(8), Vec3::new(0.0, -0.45, 0.0)),
+        ];
+        let mut pose = Pose::new();
+        for (_, parent, translation) in &joints {
+            pose.add_local_transform(Transform::new(*translation, Quat::IDENTITY, Vec3::ONE));
+            pose.add_parent(*parent);
+        }
+        let names = joints.iter().map(|(name, ..)| name.to_string()).collect();
+        Skeleton::new(pose.clone(), pose, names)
+    }
+
+    /// The hips move forward and bob while the legs swing
+    fn walk(skeleton: &Skeleton) -> Clip {
+        let mut clip = Clip::new(Some("walk"));
+        let mut hips = TransformTrack::new(1);
+        hips.position = Vector3Track::new_with_args(
+            Interpolation::Linear,
+            vec![
+                Frame::new_simple(0.0, Vec3::new(0.0, 1.0, 0.0)),
+                Frame::new_simple(0.5, Vec3::new(0.0, 0.95, 0.5)),
+                Frame::new_simple(1.0, Vec3::new(0.0, 1.0, 1.0)),
+            ],
+        );
+        clip.add_track(hips);
+        for (joint, angle) in [(4, 0.5), (5, -0.3), (7, -0.5), (8, 0.4), (2, 0.1)] {
+            let mut track = TransformTrack::new(joint);
+            track.position = Vector3Track::new_with_args(
+                Interpolation::Linear,
+                vec![
+                    Frame::new_simple(
+                        0.0,
+                        skeleton
+                            .rest_pose
+                            .local_transform(joint as usize)
+                            .translation,
+                    ),
+                    Frame::new_simple(
+                        1.0,
+                        skeleton
+                            .rest_pose
+                            .local_transform(joint as usize)
+                            .translation,
+                    ),
+                ],
+            );
+            track.rotation = crate::track::QuatTrack::new_with_args(
+                Interpolation::Linear,
+                vec![
+                    Frame::new_simple(0.0, Quat::IDENTITY),
+                    Frame::new_simple(0.5, Quat::from_rotation_x(angle)),
+                    Frame::new_simple(1.0, Quat::from_rotation_x(-angle)),
+                ],
+            );
+            clip.add_track(track);
+        }
+        clip.recalculate_duration();
+        clip
+    }
+
+    fn assert_poses_match(a: &Pose, b: &Pose) {
+        for joint in 0..a.len() {
+            let (a, b) = (a.global_transform(joint), b.global_transform(joint));
+            assert!(
+                a.translation.abs_diff_eq(b.translation, 1e-5),
+                "joint {joint}: {:?} != {:?}",
+                a.translation,
+                b.translation
+            );
+            assert!(
+                a.rotation.abs_diff_eq(b.rotation, 1e-5)
+                    || a.rotation.abs_diff_eq(-b.rotation, 1e-5)
+            );
+        }
+    }
+
+    #[test]
+    fn pose_onto_same_skeleton_is_unchanged() {
+        let skeleton = skeleton();
+        let clip = walk(&skeleton);
+        let mut retargeter = Retargeter::new(&skeleton, &skeleton);
+        assert!(retargeter.set_hips("hips"));
+
+        for time in [0.0, 0.33, 0.5, 0.8] {
+            let mut source = skeleton.rest_pose.clone();
+            clip.sample(&mut source, time);
+            let mut result = skeleton.rest_pose.clone();
+            retargeter.retarget_pose(&source, &mut result);
+            assert_poses_match(&source, &result);
+        }
+    }
+
+    #[test]
+    fn clip_onto_same_skeleton_keeps_hips_motion() {
+        let skeleton = skeleton();
+        let clip = walk(&skeleton);
+        let retargeted = Retargeter::new(&skeleton, &skeleton).retarget_clip(&clip, 30.0);
+
+        for time in [0.0, 0.33, 0.5, 0.8] {
+            let mut source = skeleton.rest_pose.clone();
+            clip.sample(&mut source, time);
+            let mut result = skeleton.rest_pose.clone();
+            retargeted.sample(&mut result, time);
+            assert_poses_match(&source, &result);
+        }
+    }
+}
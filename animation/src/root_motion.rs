@@ -0,0 +1,100 @@
+use glam::{Quat, Vec3};
+
+use math::glam_transform::Transform;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::transform_track::TransformTrack;
+
+/// The horizontal translation and yaw of a root joint, moved out of a clip so that the
+/// character can be moved by it instead
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RootMotion {
+    pub joint: u32,
+    pub up: Vec3,
+    pub extract_yaw: bool,
+    source: TransformTrack,
+    start: Transform,
+}
+
+impl RootMotion {
+    /// Takes the horizontal motion out of `track`, leaving the root where it is at `start_time`
+    pub fn extract(
+        track: &mut TransformTrack,
+        up: Vec3,
+        extract_yaw: bool,
+        start_time: f32,
+    ) -> Self {
+        let up = up.normalize();
+        let source = track.clone();
+        let mut this = Self {
+            joint: track.id,
+            up,
+            extract_yaw,
+            source,
+            start: Transform::default(),
+        };
+        this.start = this.sample(start_time);
+
+        let start_translation = this.start.translation;
+        for frame in &mut track.position.frames {
+            let value = Vec3::from_array(frame.value);
+            let value = value - this.horizontal(value) + start_translation;
+            frame.value = value.to_array();
+            for tangent in [&mut frame.in_tangent, &mut frame.out_tangent] {
+                let t = Vec3::from_array(*tangent);
+                *tangent = (t - this.horizontal(t)).to_array();
+            }
+        }
+        if extract_yaw {
+            let start_yaw = this.start.rotation;
+            for frame in &mut track.rotation.frames {
+                let value = Quat::from_array(frame.value);
+                let correction = start_yaw * this.yaw(value).inverse();
+                frame.value = (correction * value).to_array();
+                for tangent in [&mut frame.in_tangent, &mut frame.out_tangent] {
+                    *tangent = (correction * Quat::from_array(*tangent)).to_array();
+                }
+            }
+        }
+        this
+    }
+
+    pub fn optimize(&mut self, samples_per_second: f32) {
+        self.source.optimize(samples_per_second);
+    }
+
+    /// Root motion at `time`, only the horizontal translation and yaw are kept
+    pub fn sample(&self, time: f32) -> Transform {
+        let root = self.source.sample(Transform::default(), time, false);
+        let rotation = if self.extract_yaw {
+            self.yaw(root.rotation)
+        } else {
+            Quat::IDENTITY
+        };
+        Transform::new(self.horizontal(root.translation), rotation, Vec3::ONE)
+    }
+
+    /// Motion between two times within the clip, expressed relative to the root at `from`
+    pub fn delta(&self, from: f32, to: f32) -> Transform {
+        self.sample(from).inverse().combine(&self.sample(to))
+    }
+
+    fn horizontal(&self, v: Vec3) -> Vec3 {
+        v - self.up * v.dot(self.up)
+    }
+
+    /// The twist of `rotation` around the up axis
+    fn yaw(&self, rotation: Quat) -> Quat {
+        let axis = Vec3::new(rotation.x, rotation.y, rotation.z);
+        let projected = self.up * axis.dot(self.up);
+        let twist = Quat::from_xyzw(projected.x, projected.y, projected.z, rotation.w);
+        if twist.length_squared() < f32::EPSILON {
+            Quat::IDENTITY
+        } else {
+            twist.normalize()
+        }
+    }
+}
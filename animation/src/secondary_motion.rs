@@ -0,0 +1,202 @@
+use glam::{Quat, Vec3};
+
+use math::glam_transform::{FromTo, Transform};
+
+use super::pose::Pose;
+
+/// The time step the stiffness and damping of a chain are given for
+const REFERENCE_STEP: f32 = 1.0 / 60.0;
+/// Longer frames are cut short so a hitch doesn't throw the chains around
+const MAX_STEP: f32 = 1.0 / 20.0;
+
+/// A sphere that follows a joint and pushes simulated joints out of it, e.g. the head for hair
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SphereCollider {
+    pub joint: usize,
+    /// The center in the joint's local space
+    pub offset: Vec3,
+    pub radius: f32,
+}
+
+impl SphereCollider {
+    pub fn new(joint: usize, offset: Vec3, radius: f32) -> Self {
+        Self {
+            joint,
+            offset,
+            radius,
+        }
+    }
+
+    fn center(&self, model: &Transform, pose: &Pose) -> Vec3 {
+        let world = model.combine(&pose.global_transform(self.joint));
+        world.translation + world.rotation * (world.scale * self.offset)
+    }
+}
+
+/// A chain of joints, e.g. a ponytail or a tail, whose first joint follows the animation and
+/// the rest lag behind it as verlet points
+#[derive(Debug, Clone)]
+pub struct SpringChain {
+    /// Ordered from the anchored joint down to the tip
+    pub joints: Vec<usize>,
+    /// How strongly the joints are pulled back to their animated shape every 1/60 s, from 0
+    /// to 1
+    pub stiffness: f32,
+    /// How much speed the joints lose every 1/60 s, from 0 to 1
+    pub damping: f32,
+    /// In world units per second squared
+    pub gravity: Vec3,
+    points: Vec<Vec3>,
+    previous: Vec<Vec3>,
+}
+
+impl SpringChain {
+    pub fn new(joints: Vec<usize>) -> Self {
+        Self {
+            joints,
+            stiffness: 0.1,
+            damping: 0.1,
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            points: vec![],
+            previous: vec![],
+        }
+    }
+
+    /// A chain from `from` down to `to`. Returns None if `to` isn't below `from`
+    pub fn from_chain(pose: &Pose, from: usize, to: usize) -> Option<Self> {
+        let mut joints = vec![to];
+        let mut current = to;
+        while current != from {
+            current = pose.parent(current)?;
+            joints.push(current);
+        }
+        joints.reverse();
+        Some(Self::new(joints))
+    }
+
+    /// Puts the joints back where the animation has them on the next update, e.g. after the
+    /// character teleported
+    pub fn reset(&mut self) {
+        self.points.clear();
+        self.previous.clear();
+    }
+
+    fn simulate(
+        &mut self,
+        model: &Transform,
+        pose: &Pose,
+        colliders: &[(Vec3, f32)],
+        delta_time: f32,
+    ) {
+        let animated: Vec<Vec3> = self
+            .joints
+            .iter()
+            .map(|&joint| model.combine(&pose.global_transform(joint)).translation)
+            .collect();
+        if self.points.len() != animated.len() {
+            self.points.clone_from(&animated);
+            self.previous.clone_from(&animated);
+            return;
+        }
+
+        let steps = delta_time / REFERENCE_STEP;
+        let keep_speed = (1.0 - self.damping.clamp(0.0, 1.0)).powf(steps);
+        let stiffness = 1.0 - (1.0 - self.stiffness.clamp(0.0, 1.0)).powf(steps);
+        let gravity = self.gravity * delta_time * delta_time;
+
+        self.points[0] = animated[0];
+        self.previous[0] = animated[0];
+        for i in 1..self.points.len() {
+            let velocity = (self.points[i] - self.previous[i]) * keep_speed;
+            self.previous[i] = self.points[i];
+            let mut point = self.points[i] + velocity + gravity;
+
+            // Pulls towards the animated shape hanging off the simulated parent
+            let parent = self.points[i - 1];
+            let rest = parent + (animated[i] - animated[i - 1]);
+            point += (rest - point) * stiffness;
+
+            let length = animated[i].distance(animated[i - 1]);
+            point = parent + (point - parent).normalize_or_zero() * length;
+
+            for &(center, radius) in colliders {
+                let offset = point - center;
+                if offset.length_squared() < radius * radius {
+                    point = center + offset.normalize_or_zero() * radius;
+                }
+            }
+            self.points[i] = point;
+        }
+    }
+
+    /// Turns every joint but the tip so its child points at where it was simulated
+    fn write_pose(&self, model: &Transform, pose: &mut Pose) {
+        for i in 0..self.joints.len().saturating_sub(1) {
+            let (joint, child) = (self.joints[i], self.joints[i + 1]);
+            let world = model.combine(&pose.global_transform(joint));
+            let current = model.combine(&pose.global_transform(child)).translation;
+            let inv_rot = world.rotation.inverse();
+            let to_current = inv_rot * (current - world.translation);
+            let to_desired = inv_rot * (self.points[i + 1] - world.translation);
+            if to_current.length_squared() < f32::EPSILON
+                || to_desired.length_squared() < f32::EPSILON
+            {
+                continue;
+            }
+
+            let mut local = pose.local_transform(joint).clone();
+            local.rotation = (local.rotation * Quat::from_to(to_current, to_desired)).normalize();
+            pose.set_local_transform(joint, local);
+        }
+    }
+}
+
+/// Springy chains like hair and tails driven by the animated pose and how the model moves
+#[derive(Debug, Clone, Default)]
+pub struct SecondaryMotion {
+    pub chains: Vec<SpringChain>,
+    pub colliders: Vec<SphereCollider>,
+}
+
+impl SecondaryMotion {
+    pub fn new() -> Self {
+        Self {
+            chains: vec![],
+            colliders: vec![],
+        }
+    }
+
+    pub fn add_chain(&mut self, chain: SpringChain) -> usize {
+        self.chains.push(chain);
+        self.chains.len() - 1
+    }
+
+    pub fn add_collider(&mut self, collider: SphereCollider) {
+        self.colliders.push(collider);
+    }
+
+    pub fn reset(&mut self) {
+        for chain in &mut self.chains {
+            chain.reset();
+        }
+    }
+
+    /// Steps the chains and writes them into `pose`, which `model` places in the world. Call
+    /// it after the pose is otherwise final and before skinning
+    pub fn apply(&mut self, model: &Transform, pose: &mut Pose, delta_time: f32) {
+        let delta_time = delta_time.min(MAX_STEP);
+        if delta_time <= 0.0 {
+            return;
+        }
+        for chain in &mut self.chains {
+            // Colliders are placed after the chains before them so a chain can rest on another
+            let colliders: Vec<(Vec3, f32)> = self
+                .colliders
+                .iter()
+                .map(|collider| (collider.center(model, pose), collider.radius))
+                .collect();
+            chain.simulate(model, pose, &colliders, delta_time);
+            chain.write_pose(model, pose);
+        }
+    }
+}
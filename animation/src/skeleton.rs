@@ -1,13 +1,21 @@
 use glam::Mat4;
 
+use math::dual_quat::DualQuat;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use super::pose::Pose;
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "SkeletonData", into = "SkeletonData"))]
 pub struct Skeleton {
     pub rest_pose: Pose,
     pub bind_pose: Pose,
     joint_names: Vec<String>,
     pub inverse_bind_pose: Vec<Mat4>,
+    pub inverse_bind_dual_quats: Vec<DualQuat>,
 }
 
 impl Skeleton {
@@ -17,6 +25,7 @@ impl Skeleton {
             bind_pose,
             joint_names,
             inverse_bind_pose: vec![],
+            inverse_bind_dual_quats: vec![],
         };
         this.update_inverse_bind_pose();
         this
@@ -26,13 +35,32 @@ impl Skeleton {
         &self.joint_names[idx]
     }
 
+    pub fn joint_names(&self) -> &[String] {
+        &self.joint_names
+    }
+
+    pub fn joint_index(&self, name: &str) -> Option<usize> {
+        self.joint_names.iter().position(|n| n == name)
+    }
+
     pub fn inverse_bind_pose(&self) -> &Vec<Mat4> {
         &self.inverse_bind_pose
     }
 
+    /// Skinning transforms of `pose` for dual quaternion skinning
+    pub fn dual_quat_skin_palette(&self, pose: &Pose) -> Vec<DualQuat> {
+        pose.dual_quat_palette()
+            .into_iter()
+            .zip(&self.inverse_bind_dual_quats)
+            .map(|(global, inverse_bind)| global * *inverse_bind)
+            .collect()
+    }
+
     fn update_inverse_bind_pose(&mut self) {
         self.inverse_bind_pose = vec![];
         self.inverse_bind_pose.reserve(self.bind_pose.len());
+        self.inverse_bind_dual_quats = vec![];
+        self.inverse_bind_dual_quats.reserve(self.bind_pose.len());
         for i in 0..self.bind_pose.len() {
             let world = self.bind_pose.global_transform(i);
             self.inverse_bind_pose.push(
@@ -41,6 +69,35 @@ impl Skeleton {
                     /* .expect(&format!("Failed to inverse world {:?}", world)) */
                     .into(),
             );
+            self.inverse_bind_dual_quats
+                .push(DualQuat::from(&world).conjugate());
+        }
+    }
+}
+
+/// The inverse bind pose is derived from the bind pose so only the poses and names are stored
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SkeletonData {
+    rest_pose: Pose,
+    bind_pose: Pose,
+    joint_names: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+impl From<SkeletonData> for Skeleton {
+    fn from(value: SkeletonData) -> Self {
+        Skeleton::new(value.rest_pose, value.bind_pose, value.joint_names)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Skeleton> for SkeletonData {
+    fn from(value: Skeleton) -> Self {
+        Self {
+            rest_pose: value.rest_pose,
+            bind_pose: value.bind_pose,
+            joint_names: value.joint_names,
         }
     }
 }
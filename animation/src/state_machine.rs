@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use super::{clip::Clip, pose::Pose, skeleton::Skeleton};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Greater(String, f32),
+    Less(String, f32),
+    Bool(String, bool),
+    /// True once a non-looping clip has reached its end
+    ClipFinished,
+}
+
+#[derive(Clone)]
+pub struct AnimationState {
+    pub name: String,
+    pub clip: Clip,
+    pub speed: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    pub duration: f32,
+    pub conditions: Vec<Condition>,
+}
+
+impl Transition {
+    pub fn new(from: &str, to: &str, duration: f32, conditions: Vec<Condition>) -> Self {
+        Self {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            duration,
+            conditions,
+        }
+    }
+}
+
+struct ActiveTransition {
+    to: usize,
+    time: f32,
+    elapsed: f32,
+    duration: f32,
+}
+
+pub struct StateMachine {
+    rest_pose: Pose,
+    states: Vec<AnimationState>,
+    transitions: Vec<Transition>,
+    floats: HashMap<String, f32>,
+    bools: HashMap<String, bool>,
+    current: usize,
+    time: f32,
+    active_transition: Option<ActiveTransition>,
+    current_pose: Pose,
+    target_pose: Pose,
+    pose: Pose,
+}
+
+impl StateMachine {
+    pub fn new(skeleton: &Skeleton) -> Self {
+        Self {
+            rest_pose: skeleton.rest_pose.clone(),
+            states: vec![],
+            transitions: vec![],
+            floats: HashMap::new(),
+            bools: HashMap::new(),
+            current: 0,
+            time: 0.0,
+            active_transition: None,
+            current_pose: skeleton.rest_pose.clone(),
+            target_pose: skeleton.rest_pose.clone(),
+            pose: skeleton.rest_pose.clone(),
+        }
+    }
+
+    /// Adds a state playing `clip`, the first state added is the initial state
+    pub fn add_state(&mut self, name: &str, clip: Clip) {
+        if self.states.is_empty() {
+            self.time = clip.start_time;
+        }
+        self.states.push(AnimationState {
+            name: name.to_owned(),
+            clip,
+            speed: 1.0,
+        });
+    }
+
+    pub fn state_mut(&mut self, name: &str) -> Option<&mut AnimationState> {
+        self.states.iter_mut().find(|s| s.name == name)
+    }
+
+    pub fn add_transition(&mut self, transition: Transition) {
+        self.transitions.push(transition);
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.floats.insert(name.to_owned(), value);
+    }
+
+    pub fn float(&self, name: &str) -> f32 {
+        self.floats.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.bools.insert(name.to_owned(), value);
+    }
+
+    pub fn bool(&self, name: &str) -> bool {
+        self.bools.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn current_state(&self) -> Option<&str> {
+        Some(&self.states.get(self.current)?.name)
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.active_transition.is_some()
+    }
+
+    /// Jumps straight to a state without blending
+    pub fn set_state(&mut self, name: &str) -> bool {
+        match self.state_index(name) {
+            Some(idx) => {
+                self.current = idx;
+                self.time = self.states[idx].clip.start_time;
+                self.active_transition = None;
+                self.current_pose = self.rest_pose.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) -> &Pose {
+        if self.states.is_empty() {
+            return &self.pose;
+        }
+        if self.active_transition.is_none() {
+            self.start_transition();
+        }
+
+        let state = &self.states[self.current];
+        self.time = state
+            .clip
+            .sample(&mut self.current_pose, self.time + delta_time * state.speed);
+
+        match &mut self.active_transition {
+            Some(transition) => {
+                let target = &self.states[transition.to];
+                transition.time = target.clip.sample(
+                    &mut self.target_pose,
+                    transition.time + delta_time * target.speed,
+                );
+                transition.elapsed += delta_time;
+                let t = if transition.duration > 0.0 {
+                    (transition.elapsed / transition.duration).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+                self.pose
+                    .blend(&self.current_pose, &self.target_pose, t, None);
+                if t >= 1.0 {
+                    self.current = transition.to;
+                    self.time = transition.time;
+                    std::mem::swap(&mut self.current_pose, &mut self.target_pose);
+                    self.active_transition = None;
+                }
+            }
+            None => self.pose.clone_from(&self.current_pose),
+        }
+        &self.pose
+    }
+
+    fn start_transition(&mut self) {
+        let current = &self.states[self.current];
+        let transition = self.transitions.iter().find(|t| {
+            t.from == current.name
+                && t.to != current.name
+                && t.conditions.iter().all(|c| self.is_satisfied(c))
+        });
+        if let Some(transition) = transition {
+            if let Some(to) = self.state_index(&transition.to) {
+                self.target_pose = self.rest_pose.clone();
+                self.active_transition = Some(ActiveTransition {
+                    to,
+                    time: self.states[to].clip.start_time,
+                    elapsed: 0.0,
+                    duration: transition.duration,
+                });
+            }
+        }
+    }
+
+    fn is_satisfied(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Greater(name, value) => self.float(name) > *value,
+            Condition::Less(name, value) => self.float(name) < *value,
+            Condition::Bool(name, value) => self.bool(name) == *value,
+            Condition::ClipFinished => {
+                let clip = &self.states[self.current].clip;
+                !clip.looping && self.time >= clip.start_time + clip.duration()
+            }
+        }
+    }
+
+    fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|s| s.name == name)
+    }
+}
@@ -3,6 +3,9 @@ use std::ops::{Add, Mul};
 use glam::{Quat, Vec3};
 use num_traits::clamp;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use super::{
     array_type::ArrayType,
     frame::Frame,
@@ -15,9 +18,27 @@ pub type Vector3Track = Track<Vec3>;
 pub type QuatTrack = Track<Quat>;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T::Slice: Serialize",
+        deserialize = "T::Slice: Deserialize<'de>"
+    ))
+)]
 pub struct Track<T: ArrayType> {
+    /// `optimize` has to be called again after changing the frames of an optimized track
     pub frames: Vec<Frame<T>>,
     interp: Interpolation,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    sampled_frames: Vec<usize>,
+}
+
+/// The lookup table from `optimize` is left out, it doesn't change what the track samples to
+impl<T: ArrayType + PartialEq> PartialEq for Track<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.frames == other.frames && self.interp == other.interp
+    }
 }
 
 impl<T> Track<T>
@@ -35,11 +56,45 @@ where
         Self {
             frames: vec![],
             interp: Interpolation::Linear,
+            sampled_frames: vec![],
         }
     }
 
     pub fn new_with_args(interp: Interpolation, frames: Vec<Frame<T>>) -> Self {
-        Self { frames, interp }
+        Self {
+            frames,
+            interp,
+            sampled_frames: vec![],
+        }
+    }
+
+    /// Precomputes which frame is active at `samples_per_second` points in time per second so that
+    /// sampling doesn't have to search through the frames. Has to be called again if the frames change
+    pub fn optimize(&mut self, samples_per_second: f32) {
+        self.sampled_frames.clear();
+        if self.frames.len() < 2 || samples_per_second <= 0.0 {
+            return;
+        }
+        let start_time = self.frames[0].time;
+        let duration = self.frames[self.frames.len() - 1].time - start_time;
+        if duration <= 0.0 {
+            return;
+        }
+
+        let num_samples = (duration * samples_per_second).ceil() as usize + 1;
+        self.sampled_frames.reserve(num_samples);
+        let mut frame = 0;
+        for i in 0..num_samples {
+            let t = start_time + duration * (i as f32 / (num_samples - 1) as f32);
+            while frame + 1 < self.frames.len() && self.frames[frame + 1].time <= t {
+                frame += 1;
+            }
+            self.sampled_frames.push(frame);
+        }
+    }
+
+    pub fn is_optimized(&self) -> bool {
+        !self.sampled_frames.is_empty()
     }
 
     pub fn start_time(&self) -> Option<f32> {
@@ -160,6 +215,10 @@ where
             }
         }
 
+        if self.is_optimized() {
+            return Some(self.sampled_frame_index(t));
+        }
+
         // Find the index of the frame at or before the given time
         for (idx, frame) in self.frames.iter().enumerate().rev() {
             if t >= frame.time {
@@ -169,6 +228,19 @@ where
         None
     }
 
+    fn sampled_frame_index(&self, t: f32) -> usize {
+        let start_time = self.frames[0].time;
+        let duration = self.frames[self.frames.len() - 1].time - start_time;
+        let last_sample = self.sampled_frames.len() - 1;
+        let sample = ((t - start_time) / duration * last_sample as f32) as usize;
+        // The sample is at or before t so at most a few frames have to be skipped
+        let mut idx = self.sampled_frames[sample.min(last_sample)];
+        while idx + 1 < self.frames.len() && self.frames[idx + 1].time <= t {
+            idx += 1;
+        }
+        idx
+    }
+
     fn adjust_time_to_fit_track(&self, mut t: f32, looping: bool) -> f32 {
         if self.frames.is_empty() {
             return 0.0;
@@ -197,3 +269,68 @@ pub fn loop_time(mut t: f32, start_time: f32, end_time: f32) -> f32 {
     }
     t + start_time
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cubic_track() -> ScalarTrack {
+        // Two seconds between the frames so the tangents have to be scaled by the frame delta
+        ScalarTrack::new_with_args(
+            Interpolation::Cubic,
+            vec![
+                Frame::new(1.0, 0.0, 2.0, 0.0),
+                Frame::new(3.0, 1.0, 0.0, 4.0),
+            ],
+        )
+    }
+
+    #[test]
+    fn cubic_sample_matches_hermite() {
+        let track = cubic_track();
+        // s = 0.5: 4 * 0.5 + (2 * 2) * 0.125 + (1 * 2) * -0.125
+        assert!((track.sample(2.0, false) - 2.25).abs() < 1e-6);
+        // s = 0.25: 4 * 0.15625 + (2 * 2) * 0.140625 + (1 * 2) * -0.046875
+        assert!((track.sample(1.5, false) - 1.09375).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cubic_sample_hits_keyframes() {
+        let track = cubic_track();
+        assert!(track.sample(1.0, false).abs() < 1e-6);
+        assert!((track.sample(3.0, false) - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn optimized_track_equals_original() {
+        let track = cubic_track();
+        let mut optimized = track.clone();
+        optimized.optimize(7.0);
+        assert!(optimized.is_optimized());
+        assert_eq!(optimized, track);
+        assert!((optimized.sample(1.5, false) - track.sample(1.5, false)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cubic_vector_sample_matches_hermite() {
+        let track = Vector3Track::new_with_args(
+            Interpolation::Cubic,
+            vec![
+                Frame::new(0.0, Vec3::ZERO, Vec3::new(2.0, 0.0, -2.0), Vec3::ZERO),
+                Frame::new(
+                    0.5,
+                    Vec3::new(1.0, 0.0, 0.0),
+                    Vec3::ZERO,
+                    Vec3::new(4.0, 1.0, 0.0),
+                ),
+            ],
+        );
+        // s = 0.5 with a frame delta of 0.5
+        let expected = Vec3::new(
+            4.0 * 0.5 + (2.0 * 0.5) * 0.125 + (1.0 * 0.5) * -0.125,
+            0.5,
+            (-2.0 * 0.5) * 0.125,
+        );
+        assert!(track.sample(0.25, false).abs_diff_eq(expected, 1e-6));
+    }
+}
@@ -1,8 +1,12 @@
 use math::glam_transform::Transform;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use super::track::{QuatTrack, Vector3Track};
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TransformTrack {
     pub id: u32,
     pub position: Vector3Track,
@@ -46,6 +50,12 @@ impl TransformTrack {
         .reduce(f32::min)
     }
 
+    pub fn optimize(&mut self, samples_per_second: f32) {
+        self.position.optimize(samples_per_second);
+        self.rotation.optimize(samples_per_second);
+        self.scale.optimize(samples_per_second);
+    }
+
     pub fn sample(&self, ref_tf: Transform, t: f32, looping: bool) -> Transform {
         let mut result = ref_tf;
         if self.position.len() > 1 {
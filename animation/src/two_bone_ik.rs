@@ -0,0 +1,106 @@
+use glam::{Quat, Vec3};
+
+use math::glam_transform::{FromTo, Transform};
+
+use super::pose::Pose;
+
+/// Shorter bones can't be solved as the reachable range clamped to below is empty
+const MIN_BONE_LENGTH: f32 = 0.001;
+
+/// Solves a root, mid and end joint exactly with the law of cosines, e.g. hip, knee and ankle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoBoneIk {
+    pub root: usize,
+    pub mid: usize,
+    pub end: usize,
+    /// Gives the end joint the rotation of the target
+    pub match_target_rotation: bool,
+}
+
+impl TwoBoneIk {
+    pub fn new(root: usize, mid: usize, end: usize) -> Self {
+        Self {
+            root,
+            mid,
+            end,
+            match_target_rotation: false,
+        }
+    }
+
+    /// Turns the root and mid joint of `pose` so the end joint reaches `target`. The mid joint
+    /// bends towards `pole`, a direction in world space, e.g. forward for a knee and backward for
+    /// an elbow. With no usable pole the mid joint keeps bending the way it already does.
+    /// Returns false if the target is out of reach, the chain is then stretched towards it. A
+    /// bone too short to bend around leaves the pose as it is and returns false
+    pub fn solve(
+        &self,
+        model: &Transform,
+        pose: &mut Pose,
+        target: &Transform,
+        pole: Vec3,
+    ) -> bool {
+        let world = |pose: &Pose, joint: usize| model.combine(&pose.global_transform(joint));
+        let root = world(pose, self.root).translation;
+        let mid = world(pose, self.mid).translation;
+        let end = world(pose, self.end).translation;
+        let goal = target.translation;
+
+        let upper = (mid - root).length();
+        let lower = (end - mid).length();
+        if upper < MIN_BONE_LENGTH || lower < MIN_BONE_LENGTH {
+            return false;
+        }
+        let Some(direction) = (goal - root).try_normalize() else {
+            return false;
+        };
+        let distance = (goal - root).length();
+        let reachable = distance <= upper + lower && distance >= (upper - lower).abs();
+        let distance = distance.clamp((upper - lower).abs() + 0.0001, upper + lower - 0.0001);
+
+        // The angle between the root to goal line and the upper bone
+        let cos_root =
+            (upper * upper + distance * distance - lower * lower) / (2.0 * upper * distance);
+        let root_angle = cos_root.clamp(-1.0, 1.0).acos();
+
+        let bend = perpendicular(pole, direction)
+            .or_else(|| perpendicular(mid - root, direction))
+            .unwrap_or_else(|| direction.any_orthonormal_vector());
+        let desired_mid =
+            root + direction * (upper * root_angle.cos()) + bend * (upper * root_angle.sin());
+
+        rotate_joint(model, pose, self.root, mid - root, desired_mid - root);
+
+        let mid = world(pose, self.mid).translation;
+        let end = world(pose, self.end).translation;
+        rotate_joint(model, pose, self.mid, end - mid, goal - mid);
+
+        if self.match_target_rotation {
+            let parent_rotation = match pose.parent(self.end) {
+                Some(parent) => world(pose, parent).rotation,
+                None => model.rotation,
+            };
+            let mut local = pose.local_transform(self.end).clone();
+            local.rotation = parent_rotation.inverse() * target.rotation;
+            pose.set_local_transform(self.end, local);
+        }
+
+        reachable
+    }
+}
+
+/// The part of `v` at a right angle to `direction`
+fn perpendicular(v: Vec3, direction: Vec3) -> Option<Vec3> {
+    (v - direction * v.dot(direction)).try_normalize()
+}
+
+/// Turns `joint` so that the world space vector `from` ends up pointing along `to`
+fn rotate_joint(model: &Transform, pose: &mut Pose, joint: usize, from: Vec3, to: Vec3) {
+    let inv_rot = model
+        .combine(&pose.global_transform(joint))
+        .rotation
+        .inverse();
+    let delta = Quat::from_to(inv_rot * from, inv_rot * to);
+    let mut local = pose.local_transform(joint).clone();
+    local.rotation = (local.rotation * delta).normalize();
+    pose.set_local_transform(joint, local);
+}
@@ -0,0 +1,53 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::track::ScalarTrack;
+
+/// Animates the morph target weights of a single node, one track per morph target
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WeightsTrack {
+    pub id: u32,
+    pub weights: Vec<ScalarTrack>,
+}
+
+impl WeightsTrack {
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            weights: vec![],
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.weights.iter().any(|w| w.len() > 0)
+    }
+
+    pub fn start_time(&self) -> Option<f32> {
+        self.weights
+            .iter()
+            .filter_map(|w| w.start_time())
+            .reduce(f32::min)
+    }
+
+    pub fn end_time(&self) -> Option<f32> {
+        self.weights
+            .iter()
+            .filter_map(|w| w.end_time())
+            .reduce(f32::max)
+    }
+
+    pub fn optimize(&mut self, samples_per_second: f32) {
+        for track in &mut self.weights {
+            track.optimize(samples_per_second);
+        }
+    }
+
+    pub fn sample(&self, out_weights: &mut [f32], t: f32, looping: bool) {
+        for (weight, track) in out_weights.iter_mut().zip(&self.weights) {
+            if track.len() > 1 {
+                *weight = track.sample(t, looping);
+            }
+        }
+    }
+}
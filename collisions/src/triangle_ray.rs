@@ -5,6 +5,14 @@ pub struct Ray {
     pub direction: Vec3,
 }
 
+/// Where a ray hit a triangle, `normal` faces back towards the ray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
 pub struct Vertex {
     pub position: [f32; 3],
 }
@@ -17,6 +25,10 @@ impl Ray {
         }
     }
     pub fn cast(&self, triangle: &Triangle) -> Option<Vec3> {
+        self.hit(triangle).map(|hit| hit.point)
+    }
+
+    pub fn hit(&self, triangle: &Triangle) -> Option<RayHit> {
         const EPSILON: f32 = 0.0000001;
         let (v0, v1, v2) = (triangle.v0, triangle.v1, triangle.v2);
         let edge1 = v1 - v0;
@@ -43,7 +55,24 @@ impl Ray {
         if t <= EPSILON {
             return None;
         }
-        Some(origin + ray_vector * t)
+        let normal = if triangle.normal.dot(ray_vector) > 0.0 {
+            -triangle.normal
+        } else {
+            triangle.normal
+        };
+        Some(RayHit {
+            point: origin + ray_vector * t,
+            normal,
+            distance: t * ray_vector.length(),
+        })
+    }
+
+    /// The nearest hit among `triangles`
+    pub fn closest_hit(&self, triangles: &[Triangle]) -> Option<RayHit> {
+        triangles
+            .iter()
+            .filter_map(|triangle| self.hit(triangle))
+            .reduce(|a, b| if b.distance < a.distance { b } else { a })
     }
 }
 
@@ -63,6 +92,10 @@ impl Triangle {
             normal: Vec3::cross(v1 - v0, v2 - v0).normalize(),
         }
     }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
 }
 
 pub fn mesh_to_triangles(vertices: &[Vertex], indices: &[u32]) -> Vec<Triangle> {
@@ -1,6 +1,5 @@
 use std::sync::{Arc, RwLock};
 
-use animation::pose::Pose;
 use gameengine_rs::resources::load_texture;
 use gameengine_rs::run;
 use gameengine_rs::state::State;
@@ -36,16 +35,12 @@ pub fn main() {
         .find(|c| c.name == "Walking")
         .unwrap()
         .to_owned();
-    let time_a = clip_a.start_time;
     let clip_b = animation_clips
         .iter()
         .find(|c| c.name == "Running")
         .unwrap()
         .to_owned();
-    let time_b = clip_b.start_time;
     let current_pose = skeleton.rest_pose.clone();
-    let pose_a = skeleton.rest_pose.clone();
-    let pose_b = skeleton.rest_pose.clone();
     let mut state = pollster::block_on(State::new(window));
     let diffuse_texture =
         pollster::block_on(load_texture("Woman.png", &state.device, &state.queue))
@@ -72,13 +67,8 @@ pub fn main() {
         diffuse_texture.clone(),
         skeleton.clone(),
         instances1,
-        current_pose.clone(),
         clip_a,
         clip_b,
-        time_a,
-        time_b,
-        pose_a,
-        pose_b,
     ))
     .unwrap();
 
@@ -91,8 +81,6 @@ pub fn main() {
         .iter()
         .map(|a| a.name.clone())
         .collect::<Vec<String>>());
-    let add_pose = skeleton.rest_pose.clone();
-    let additive_base = Pose::make_additive(&skeleton, &animation_clips[additive_index]);
     let instances2 = Arc::new(RwLock::new(vec![Instance {
         position: Vector3 {
             x: -2.0,
@@ -115,11 +103,8 @@ pub fn main() {
         skeleton,
         instances2,
         current_pose,
-        add_pose,
-        additive_base,
-        animation_clips,
-        clip_index,
-        additive_index,
+        animation_clips[clip_index].clone(),
+        animation_clips[additive_index].clone(),
     ))
     .unwrap();
 
@@ -1,4 +1,6 @@
-use animation::{fabrik_solver::FabrikSolver, frame::Frame, transform_track::TransformTrack};
+use animation::{
+    fabrik_solver::FabrikSolver, frame::Frame, ik_solver::IkSolver, transform_track::TransformTrack,
+};
 use gameengine_rs::camera::CameraPerspective;
 use gameengine_rs::run;
 use gameengine_rs::state::State;
@@ -1,9 +1,6 @@
 use animation::{
-    clip::Clip,
-    frame::Frame,
-    ik_leg::IkLeg,
-    interpolation::Interpolation,
-    track::{ScalarTrack, Vector3Track},
+    clip::Clip, foot_contact::ContactSettings, frame::Frame, ik_leg::IkLeg,
+    interpolation::Interpolation, track::Vector3Track,
 };
 use collisions::triangle_ray::{mesh_to_triangles, Vertex};
 use gameengine_rs::state::State;
@@ -12,7 +9,10 @@ use glam::Vec3;
 use math::{quaternion::Quaternion, vector3::Vector3};
 use num_traits::Zero;
 use rendering::{
-    gltf_loader::{load_animation_clips, load_skeleton, load_skinned_meshes, load_static_meshes},
+    gltf_loader::{
+        load_animation_clips, load_morph_targets, load_skeleton, load_skinned_meshes,
+        load_static_meshes,
+    },
     instance::Instance,
     model::{self, Material, Model},
     render_players::ik_leg_player::IkLegPlayer,
@@ -139,6 +139,7 @@ pub fn main() {
         current_clip.clone(),
         skeleton.clone(),
         instances,
+        load_morph_targets(&document, &buffers),
     ))
     .unwrap();
 
@@ -150,14 +151,7 @@ pub fn main() {
         0.2,
         &skeleton,
     );
-    let left_track = &mut left_leg.pin_track;
-    let frames = vec![
-        Frame::new_simple(0.0, 0.0),
-        Frame::new_simple(0.4, 1.0),
-        Frame::new_simple(0.6, 1.0),
-        Frame::new_simple(1.0, 0.0),
-    ];
-    *left_track = ScalarTrack::new_with_args(Interpolation::Cubic, frames);
+    left_leg.detect_pin_track(&current_clip, &skeleton, &ContactSettings::default());
     let mut right_leg = IkLeg::new(
         "RightUpLeg",
         "RightLeg",
@@ -166,14 +160,7 @@ pub fn main() {
         0.2,
         &skeleton,
     );
-    let right_track = &mut right_leg.pin_track;
-    let frames = vec![
-        Frame::new_simple(0.0, 1.0),
-        Frame::new_simple(0.3, 0.0),
-        Frame::new_simple(0.7, 0.0),
-        Frame::new_simple(1.0, 1.0),
-    ];
-    *right_track = ScalarTrack::new_with_args(Interpolation::Cubic, frames);
+    right_leg.detect_pin_track(&current_clip, &skeleton, &ContactSettings::default());
     let frames = vec![
         Frame::new_simple(0.0, Vec3::new(0.0, 0.0, 1.0)),
         Frame::new_simple(1.0, Vec3::new(0.0, 0.0, 10.0)),
@@ -5,7 +5,7 @@ use gameengine_rs::state::State;
 use gameengine_rs::{resources::load_texture, run};
 use math::{quaternion::Quaternion, vector3::Vector3};
 use rendering::{
-    gltf_loader::{load_animation_clips, load_skeleton, load_skinned_meshes},
+    gltf_loader::{load_animation_clips, load_morph_targets, load_skeleton, load_skinned_meshes},
     instance::Instance,
     renderable::Renderable,
     skeletal_model::SkeletalModel,
@@ -52,6 +52,7 @@ pub fn main() {
         current_clip,
         skeleton,
         instances,
+        load_morph_targets(&document, &buffers),
     ))
     .unwrap();
     state.add_renderable(Renderable::SkeletalModel(model));
@@ -0,0 +1,218 @@
+use std::ops::{Add, Mul};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Quat, Vec3};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::glam_transform::Transform;
+
+/// A rigid transform stored as a dual quaternion. Scale can't be represented and is dropped
+#[repr(C)]
+#[derive(Debug, PartialEq, Pod, Clone, Copy, Zeroable)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DualQuat {
+    pub real: Quat,
+    pub dual: Quat,
+}
+
+impl DualQuat {
+    pub const IDENTITY: Self = Self {
+        real: Quat::IDENTITY,
+        dual: Quat::from_xyzw(0.0, 0.0, 0.0, 0.0),
+    };
+
+    pub fn new(rotation: Quat, translation: Vec3) -> Self {
+        let real = rotation.normalize();
+        let t = Quat::from_xyzw(translation.x, translation.y, translation.z, 0.0);
+        Self {
+            real,
+            dual: (t * real) * 0.5,
+        }
+    }
+
+    pub fn rotation(&self) -> Quat {
+        self.real
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        let t = (self.dual * 2.0) * self.real.conjugate();
+        Vec3::new(t.x, t.y, t.z)
+    }
+
+    pub fn dot(&self, other: &Self) -> f32 {
+        self.real.dot(other.real)
+    }
+
+    pub fn normalize(&self) -> Self {
+        let length = self.real.length();
+        if length == 0.0 {
+            return Self::IDENTITY;
+        }
+        Self {
+            real: self.real / length,
+            dual: self.dual / length,
+        }
+    }
+
+    /// The inverse of a normalized dual quaternion
+    pub fn conjugate(&self) -> Self {
+        Self {
+            real: self.real.conjugate(),
+            dual: self.dual.conjugate(),
+        }
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.real * point + self.translation()
+    }
+
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.real * vector
+    }
+}
+
+impl Default for DualQuat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Combines like matrices, `a * b` applies `b` first
+impl Mul for DualQuat {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real * rhs.real,
+            dual: self.real * rhs.dual + self.dual * rhs.real,
+        }
+    }
+}
+
+impl Mul<f32> for DualQuat {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self {
+            real: self.real * rhs,
+            dual: self.dual * rhs,
+        }
+    }
+}
+
+impl Add for DualQuat {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real + rhs.real,
+            dual: self.dual + rhs.dual,
+        }
+    }
+}
+
+impl From<&Transform> for DualQuat {
+    fn from(value: &Transform) -> Self {
+        Self::new(value.rotation, value.translation)
+    }
+}
+
+impl From<Mat4> for DualQuat {
+    fn from(value: Mat4) -> Self {
+        let (_, rotation, translation) = value.to_scale_rotation_translation();
+        Self::new(rotation, translation)
+    }
+}
+
+impl From<DualQuat> for Transform {
+    fn from(value: DualQuat) -> Self {
+        Transform::new(value.translation(), value.rotation(), Vec3::ONE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transforms() -> [Transform; 2] {
+        [
+            Transform::new(
+                Vec3::new(1.0, -2.0, 0.5),
+                Quat::from_euler(glam::EulerRot::XYZ, 0.3, -1.1, 2.0),
+                Vec3::ONE,
+            ),
+            Transform::new(
+                Vec3::new(-0.4, 0.0, 3.0),
+                Quat::from_rotation_y(0.9),
+                Vec3::ONE,
+            ),
+        ]
+    }
+
+    fn assert_same(a: &DualQuat, b: &DualQuat) {
+        let points = [Vec3::ZERO, Vec3::X, Vec3::new(0.3, -2.0, 1.5)];
+        for point in points {
+            assert!(a
+                .transform_point(point)
+                .abs_diff_eq(b.transform_point(point), 1e-5));
+        }
+    }
+
+    #[test]
+    fn transform_point_matches_matrix() {
+        for transform in transforms() {
+            let matrix = Mat4::from_rotation_translation(transform.rotation, transform.translation);
+            let dq = DualQuat::from(&transform);
+            for point in [
+                Vec3::ZERO,
+                Vec3::new(1.0, 2.0, 3.0),
+                Vec3::new(-0.5, 0.1, 4.0),
+            ] {
+                assert!(dq
+                    .transform_point(point)
+                    .abs_diff_eq(matrix.transform_point3(point), 1e-5));
+                assert!(dq
+                    .transform_vector(point)
+                    .abs_diff_eq(matrix.transform_vector3(point), 1e-5));
+            }
+            assert_same(&DualQuat::from(matrix), &dq);
+        }
+    }
+
+    #[test]
+    fn mul_and_conjugate_match_transform() {
+        let [a, b] = transforms();
+        let product = DualQuat::from(&a) * DualQuat::from(&b);
+        assert_same(&product, &DualQuat::from(&a.combine(&b)));
+        assert_same(
+            &DualQuat::from(&a).conjugate(),
+            &DualQuat::from(&a.inverse()),
+        );
+        assert_same(
+            &(DualQuat::from(&a) * DualQuat::from(&a).conjugate()),
+            &DualQuat::IDENTITY,
+        );
+    }
+
+    #[test]
+    fn normalize_weighted_blend() {
+        let rotation = Quat::from_rotation_z(0.7);
+        let a = DualQuat::new(rotation, Vec3::new(1.0, 0.0, 0.0));
+        let b = DualQuat::new(rotation, Vec3::new(3.0, 2.0, 0.0));
+        // Weights that don't add up to 1 are scaled back by normalizing
+        let blend = (a * 0.2 + b * 0.2).normalize();
+        assert!((blend.real.length() - 1.0).abs() < 1e-6);
+        assert!(blend.rotation().abs_diff_eq(rotation, 1e-6));
+        assert!(blend
+            .translation()
+            .abs_diff_eq(Vec3::new(2.0, 1.0, 0.0), 1e-5));
+
+        let [c, d] = transforms().map(|t| DualQuat::from(&t));
+        let blend = (c * 0.3 + d * 0.7).normalize();
+        assert!((blend.real.length() - 1.0).abs() < 1e-6);
+        assert_eq!(DualQuat::default().normalize(), DualQuat::IDENTITY);
+        assert_eq!((DualQuat::IDENTITY * 0.0).normalize(), DualQuat::IDENTITY);
+    }
+}
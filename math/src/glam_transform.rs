@@ -1,6 +1,10 @@
 use glam::{Mat4, Quat, Vec3};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Transform {
     pub translation: Vec3,
     pub rotation: Quat,
@@ -1,3 +1,4 @@
+pub mod dual_quat;
 pub mod glam_transform;
 pub mod matrix3;
 pub mod matrix4;
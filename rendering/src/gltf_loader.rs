@@ -3,11 +3,12 @@ use std::collections::HashMap;
 use bytemuck::Zeroable;
 use glam::{Mat4, Quat, Vec3};
 use gltf::animation::util::ReadOutputs;
-use gltf::{animation::Channel, buffer::Data, Document};
-use gltf::{Material, Node, Skin};
+use gltf::json::Value;
+use gltf::{animation::Channel, buffer::Data, Animation, Document};
+use gltf::{Material, Mesh, Node, Skin};
 
 use crate::model::ModelVertex;
-use crate::skeletal_model::SkeletalVertex;
+use crate::skeletal_model::{MorphTarget, MorphTargets, SkeletalVertex};
 use math::glam_transform::Transform;
 
 use animation::skeleton::Skeleton;
@@ -105,26 +106,56 @@ pub fn load_animation_clips(data: &Document, buffer_data: &Vec<Data>) -> Vec<Cli
                 results.push(Clip::new(name));
             }
             let (frames, interp) = frames_from_channel(&channel, buffer_data);
-            let transform_track = results[i].transform_track(node_id);
 
             match frames {
                 TransformComponentVec::Translation(t) => {
-                    transform_track.position = Track::new_with_args(interp, t);
+                    results[i].transform_track(node_id).position = Track::new_with_args(interp, t);
                 }
                 TransformComponentVec::Rotation(r) => {
-                    transform_track.rotation = Track::new_with_args(interp, r);
+                    results[i].transform_track(node_id).rotation = Track::new_with_args(interp, r);
                 }
                 TransformComponentVec::Scale(s) => {
-                    transform_track.scale = Track::new_with_args(interp, s);
+                    results[i].transform_track(node_id).scale = Track::new_with_args(interp, s);
+                }
+                TransformComponentVec::MorphTargetWeights(w) => {
+                    results[i].weights_track(node_id).weights = w
+                        .into_iter()
+                        .map(|frames| Track::new_with_args(interp, frames))
+                        .collect();
                 }
             };
 
             results[i].recalculate_duration();
         }
+        if let Some(clip) = results.get_mut(i) {
+            load_clip_events(&animation, clip);
+        }
     }
     results
 }
 
+/// Events are read from the animation's extras, e.g.
+/// `"extras": { "events": [{ "time": 0.4, "name": "footstep_left" }] }`
+fn load_clip_events(animation: &Animation, clip: &mut Clip) {
+    let extras = match animation.extras() {
+        Some(extras) => extras,
+        None => return,
+    };
+    let extras: Value = match gltf::json::deserialize::from_str(extras.get()) {
+        Ok(extras) => extras,
+        Err(e) => {
+            log::warn!("Failed to parse extras of animation {:?}: {}", clip.name, e);
+            return;
+        }
+    };
+    for event in extras["events"].as_array().into_iter().flatten() {
+        match (event["time"].as_f64(), event["name"].as_str()) {
+            (Some(time), Some(name)) => clip.add_event(time as f32, name),
+            _ => log::warn!("Ignoring malformed event {} in {:?}", event, clip.name),
+        }
+    }
+}
+
 pub fn load_skinned_meshes<'a>(
     data: &'a Document,
     buffer_data: &Vec<Data>,
@@ -138,7 +169,7 @@ pub fn load_skinned_meshes<'a>(
     let skin = &data.skins().collect::<Vec<Skin>>()[0];
     let skin_joints: Vec<Node> = skin.joints().collect();
     let mut vertices = vec![];
-    for mesh in data.meshes() {
+    for (_, mesh) in skinned_meshes(data) {
         for primitive in mesh.primitives() {
             let reader = primitive.reader(|buffer| Some(&buffer_data[buffer.index()]));
             let positions: Vec<[f32; 3]> = reader
@@ -204,6 +235,39 @@ pub fn load_skinned_meshes<'a>(
     panic!("GLTF didn't have any primitives");
 }
 
+/// Meshes of nodes that have a skin together with the index of the node
+fn skinned_meshes(data: &Document) -> impl Iterator<Item = (u32, Mesh)> {
+    data.nodes()
+        .filter(|node| node.skin().is_some())
+        .filter_map(|node| Some((node.index() as u32, node.mesh()?)))
+}
+
+/// Loads the morph targets of the primitive `load_skinned_meshes` loads the vertices from
+pub fn load_morph_targets(data: &Document, buffer_data: &Vec<Data>) -> MorphTargets {
+    let Some((node, mesh)) = skinned_meshes(data).next() else {
+        return MorphTargets::default();
+    };
+    let Some(primitive) = mesh.primitives().next() else {
+        return MorphTargets::default();
+    };
+    let reader = primitive.reader(|buffer| Some(&buffer_data[buffer.index()]));
+    let mut targets = vec![];
+    for (positions, normals, _tangents) in reader.read_morph_targets() {
+        let positions: Vec<[f32; 3]> = positions.map(|p| p.collect()).unwrap_or_default();
+        let normals: Vec<[f32; 3]> = normals.map(|n| n.collect()).unwrap_or_default();
+        targets.push(MorphTarget { positions, normals });
+    }
+    let weights = match mesh.weights() {
+        Some(weights) => weights.to_vec(),
+        None => vec![0.0; targets.len()],
+    };
+    MorphTargets {
+        node: Some(node),
+        targets,
+        weights,
+    }
+}
+
 pub fn load_static_meshes<'a>(
     data: &'a Document,
     buffer_data: &Vec<Data>,
@@ -250,6 +314,7 @@ pub enum TransformComponentVec {
     Translation(Vec<Frame<Vec3>>),
     Rotation(Vec<Frame<Quat>>),
     Scale(Vec<Frame<Vec3>>),
+    MorphTargetWeights(Vec<Vec<Frame<f32>>>),
 }
 
 fn frames_from_channel(
@@ -281,36 +346,17 @@ fn frames_from_channel(
         ReadOutputs::Rotations(fs) => {
             let fs = fs.into_f32();
             let fs: Vec<[f32; 4]> = fs.collect();
-            let mut frames = vec![];
-            assert_eq!(fs.len(), timeline_floats.len());
-            if !is_sampler_cubic {
-                for i in 0..timeline_floats.len() {
-                    let time = timeline_floats[i];
-                    let value = Quat::from_slice(&fs[i]);
-                    frames.push(Frame::new(time, Quat::zeroed(), Quat::zeroed(), value));
-                }
-            } else {
-                todo!("Implement cubic sampling");
-                /* for i in 0..timeline_floats.len() {
-                    let time = timeline_floats[i];
-                    //TODO: Decide how last value should be handled
-                    let value = if let Some(value) = fs.get(i + 1) {
-                        Quat::from_slice(value)
-                    } else {
-                        Quat::zeroed()
-                        /* Quat::from_slice(&fs[0]) */
-                    };
-                    let (in_tangent, out_tangent) = if is_sampler_cubic {
-                        (Quat::from_slice(&fs[i]), Quat::from_slice(&fs[i + 2]))
-                    } else {
-                        (Quat::zeroed(), Quat::zeroed())
-                    };
-                    frames.push(Frame::new(time, in_tangent, out_tangent, value));
-                } */
-            }
+            let frames = frames_from_channel_quat(timeline_floats, fs, is_sampler_cubic);
             (TransformComponentVec::Rotation(frames), interpolation)
         }
-        gltf::animation::util::ReadOutputs::MorphTargetWeights(_) => todo!(),
+        ReadOutputs::MorphTargetWeights(ws) => {
+            let ws: Vec<f32> = ws.into_f32().collect();
+            let frames = frames_from_channel_weights(timeline_floats, ws, is_sampler_cubic);
+            (
+                TransformComponentVec::MorphTargetWeights(frames),
+                interpolation,
+            )
+        }
     }
 }
 
@@ -321,28 +367,141 @@ fn frames_from_channel_vec3(
 ) -> Vec<Frame<Vec3>> {
     let mut frames = vec![];
     if !is_sampler_cubic {
+        assert_eq!(fs.len(), timeline_floats.len());
         for i in 0..timeline_floats.len() {
             let time = timeline_floats[i];
             let value = Vec3::from_slice(&fs[i]);
             frames.push(Frame::new(time, Vec3::ZERO, Vec3::ZERO, value));
         }
     } else {
-        todo!("Implement cubic sampling");
-        /* for i in 0..timeline_floats.len() {
+        // Cubic spline outputs are stored as (in_tangent, value, out_tangent) per keyframe
+        assert_eq!(fs.len(), timeline_floats.len() * 3);
+        for i in 0..timeline_floats.len() {
             let time = timeline_floats[i];
-            //TODO: Decide how last value should be handled
-            let value = if let Some(value) = fs.get(i + 1) {
-                Vec3::from_slice(value)
-            } else {
-                Vec3::from_slice(&fs[0])
-            };
-            let (in_tangent, out_tangent) = if is_sampler_cubic {
-                (Vec3::from_slice(&fs[i]), Vec3::from_slice(&fs[i + 2]))
+            let in_tangent = Vec3::from_slice(&fs[i * 3]);
+            let value = Vec3::from_slice(&fs[i * 3 + 1]);
+            let out_tangent = Vec3::from_slice(&fs[i * 3 + 2]);
+            frames.push(Frame::new(time, in_tangent, out_tangent, value));
+        }
+    }
+    frames
+}
+
+fn frames_from_channel_quat(
+    timeline_floats: Vec<f32>,
+    fs: Vec<[f32; 4]>,
+    is_sampler_cubic: bool,
+) -> Vec<Frame<Quat>> {
+    let mut frames = vec![];
+    if !is_sampler_cubic {
+        assert_eq!(fs.len(), timeline_floats.len());
+        for i in 0..timeline_floats.len() {
+            let time = timeline_floats[i];
+            let value = Quat::from_slice(&fs[i]);
+            frames.push(Frame::new(time, Quat::zeroed(), Quat::zeroed(), value));
+        }
+    } else {
+        // Cubic spline outputs are stored as (in_tangent, value, out_tangent) per keyframe
+        assert_eq!(fs.len(), timeline_floats.len() * 3);
+        for i in 0..timeline_floats.len() {
+            let time = timeline_floats[i];
+            let in_tangent = Quat::from_slice(&fs[i * 3]);
+            let value = Quat::from_slice(&fs[i * 3 + 1]);
+            let out_tangent = Quat::from_slice(&fs[i * 3 + 2]);
+            frames.push(Frame::new(time, in_tangent, out_tangent, value));
+        }
+    }
+    frames
+}
+
+fn frames_from_channel_weights(
+    timeline_floats: Vec<f32>,
+    ws: Vec<f32>,
+    is_sampler_cubic: bool,
+) -> Vec<Vec<Frame<f32>>> {
+    if timeline_floats.is_empty() {
+        return vec![];
+    }
+    // Weights are stored per keyframe for every morph target, for cubic splines as
+    // (in_tangents, values, out_tangents) per keyframe
+    let values_per_frame = if is_sampler_cubic { 3 } else { 1 };
+    let num_targets = ws.len() / (timeline_floats.len() * values_per_frame);
+    assert_eq!(
+        ws.len(),
+        timeline_floats.len() * values_per_frame * num_targets
+    );
+    let mut frames = vec![Vec::with_capacity(timeline_floats.len()); num_targets];
+    for (i, &time) in timeline_floats.iter().enumerate() {
+        let key = &ws[i * values_per_frame * num_targets..(i + 1) * values_per_frame * num_targets];
+        for (target, target_frames) in frames.iter_mut().enumerate() {
+            let frame = if is_sampler_cubic {
+                let in_tangent = key[target];
+                let value = key[num_targets + target];
+                let out_tangent = key[num_targets * 2 + target];
+                Frame::new(time, in_tangent, out_tangent, value)
             } else {
-                (Vec3::ZERO, Vec3::ZERO)
+                Frame::new(time, 0.0, 0.0, key[target])
             };
-            frames.push(Frame::new(time, in_tangent, out_tangent, value));
-        } */
+            target_frames.push(frame);
+        }
     }
     frames
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cubic_vec3_outputs_are_unpacked() {
+        let fs = vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 2.0, 3.0],
+            [2.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [5.0, 2.0, 3.0],
+            [0.0, 0.0, 0.0],
+        ];
+        let frames = frames_from_channel_vec3(vec![0.0, 2.0], fs, true);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].time, 0.0);
+        assert_eq!(frames[0].in_tangent, [0.0, 0.0, 0.0]);
+        assert_eq!(frames[0].value, [1.0, 2.0, 3.0]);
+        assert_eq!(frames[0].out_tangent, [2.0, 0.0, 0.0]);
+        assert_eq!(frames[1].time, 2.0);
+        assert_eq!(frames[1].in_tangent, [1.0, 0.0, 0.0]);
+        assert_eq!(frames[1].value, [5.0, 2.0, 3.0]);
+        assert_eq!(frames[1].out_tangent, [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn cubic_quat_outputs_are_unpacked() {
+        let fs = vec![
+            [0.1, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+            [0.2, 0.0, 0.0, 0.0],
+        ];
+        let frames = frames_from_channel_quat(vec![0.5], fs, true);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].in_tangent, [0.1, 0.0, 0.0, 0.0]);
+        assert_eq!(frames[0].value, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(frames[0].out_tangent, [0.2, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn cubic_vec3_track_samples_hermite() {
+        // Two seconds between the keyframes, the tangents are per second
+        let fs = vec![
+            [0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [4.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0],
+        ];
+        let frames = frames_from_channel_vec3(vec![1.0, 3.0], fs, true);
+        let track = Track::new_with_args(Interpolation::Cubic, frames);
+        // s = 0.5: 4 * 0.5 + (2 * 2) * 0.125 + (1 * 2) * -0.125
+        assert!((track.sample(2.0, false).x - 2.25).abs() < 1e-6);
+    }
+}
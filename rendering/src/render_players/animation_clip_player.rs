@@ -4,7 +4,7 @@ use wgpu::{
 };
 
 use crate::texture;
-use animation::{clip::Clip, pose::Pose};
+use animation::{clip::Clip, event::ClipEvent, pose::Pose};
 
 use super::super::{
     line::LineRender,
@@ -15,6 +15,7 @@ pub struct AnimationClipPlayer {
     playback_time: f32,
     clip: Clip,
     pose: Pose,
+    events: Vec<ClipEvent>,
 }
 
 impl AnimationClipPlayer {
@@ -45,12 +46,23 @@ impl AnimationClipPlayer {
             playback_time: 0.0,
             clip,
             pose,
+            events: vec![],
         }
     }
+
+    /// Events passed during the last update
+    pub fn events(&self) -> impl Iterator<Item = &ClipEvent> {
+        self.events.iter()
+    }
 }
 
 impl RenderableT for AnimationClipPlayer {
     fn update(&mut self, delta_time: f32, queue: &Queue) {
+        self.events = self
+            .clip
+            .events_between(self.playback_time, delta_time)
+            .cloned()
+            .collect();
         let time = self.playback_time + delta_time;
         self.playback_time = self.clip.sample(&mut self.pose, time);
         let vertices = from_pose(&self.pose, [0.0, 1.0, 0.0]);
@@ -4,10 +4,16 @@ use crate::{
     {
         model::{DrawModel, Model},
         renderable::RenderableT,
-        skeletal_model::{new_skeletal_pipeline, SkeletalModelBase, SkeletalVertex},
+        skeletal_model::{new_skeletal_pipeline, MorphTargets, SkeletalModelBase, SkeletalVertex},
     },
 };
-use animation::{clip::Clip, pose::Pose, skeleton::Skeleton};
+use animation::{
+    additive::{AdditiveDriver, AdditiveLayer, AdditiveReference, AdditiveStack},
+    clip::Clip,
+    cross_fade::CrossFadeController,
+    pose::Pose,
+    skeleton::Skeleton,
+};
 use std::sync::{Arc, RwLock};
 
 use anyhow::{Ok, Result};
@@ -19,30 +25,23 @@ struct Base {
     model: Model<SkeletalVertex>,
     camera_bind_group: BindGroup,
     pose_bind_group: BindGroup,
+    morph_bind_group: BindGroup,
     instance_buffer: wgpu::Buffer,
     animated_buffer: wgpu::Buffer,
     skeleton: Arc<Skeleton>,
 }
 
 struct BlendBetweenClips {
-    pose: Pose,
-    clip_a: Clip,
-    clip_b: Clip,
-    time_a: f32,
-    time_b: f32,
-    pose_a: Pose,
-    pose_b: Pose,
-    blend_time: f32,
-    invert_blend: bool,
+    controller: CrossFadeController,
+    clips: [Clip; 2],
+    current_clip: usize,
+    fade_timer: f32,
 }
 
 struct LayeredAnimation {
     current_pose: Pose,
-    add_pose: Pose,
-    additive_base: Pose,
-    clips: Vec<Clip>,
-    clip_index: usize,
-    additive_index: usize,
+    clip: Clip,
+    additive: AdditiveStack,
     playback_time: f32,
     additive_time: f32,
     additive_direction: f32,
@@ -72,23 +71,21 @@ impl BlenderPlayer {
         diffuse_texture: Arc<RwLock<texture::Texture>>,
         skeleton: Arc<Skeleton>,
         instances: Arc<RwLock<Vec<Instance>>>,
-        pose: Pose,
         clip_a: Clip,
         clip_b: Clip,
-        time_a: f32,
-        time_b: f32,
-        pose_a: Pose,
-        pose_b: Pose,
     ) -> Result<Self> {
         let SkeletalModelBase {
             render_pipeline,
+            dual_quat_render_pipeline: _,
             model,
             camera_bind_group,
             pose_bind_group,
+            morph_bind_group,
             original_positions: _,
             original_normals: _,
             instance_buffer,
             animated_buffer,
+            morph_buffer: _,
         } = {
             let instances = instances.read().unwrap();
             new_skeletal_pipeline(
@@ -103,28 +100,27 @@ impl BlenderPlayer {
                 &material,
                 diffuse_texture,
                 &instances,
+                &MorphTargets::default(),
             )
         };
+        let mut controller = CrossFadeController::new(&skeleton);
+        controller.play(clip_a.clone());
         Ok(Self {
             base: Base {
                 render_pipeline,
                 model,
                 camera_bind_group,
                 pose_bind_group,
+                morph_bind_group,
                 instance_buffer,
                 animated_buffer,
                 skeleton,
             },
             method: Method::BlendBetweenClips(BlendBetweenClips {
-                pose,
-                clip_a,
-                clip_b,
-                time_a,
-                time_b,
-                pose_a,
-                pose_b,
-                blend_time: 0.0,
-                invert_blend: false,
+                controller,
+                clips: [clip_a, clip_b],
+                current_clip: 0,
+                fade_timer: 0.0,
             }),
         })
     }
@@ -143,21 +139,21 @@ impl BlenderPlayer {
         skeleton: Arc<Skeleton>,
         instances: Arc<RwLock<Vec<Instance>>>,
         current_pose: Pose,
-        add_pose: Pose,
-        additive_base: Pose,
-        clips: Vec<Clip>,
-        clip_index: usize,
-        additive_index: usize,
+        clip: Clip,
+        additive_clip: Clip,
     ) -> Result<Self> {
         let SkeletalModelBase {
             render_pipeline,
+            dual_quat_render_pipeline: _,
             model,
             camera_bind_group,
             pose_bind_group,
+            morph_bind_group,
             original_positions: _,
             original_normals: _,
             instance_buffer,
             animated_buffer,
+            morph_buffer: _,
         } = {
             let instances = instances.read().unwrap();
             new_skeletal_pipeline(
@@ -172,25 +168,31 @@ impl BlenderPlayer {
                 &material,
                 diffuse_texture,
                 &instances,
+                &MorphTargets::default(),
             )
         };
+        let mut additive = AdditiveStack::new();
+        additive.add_layer(AdditiveLayer::new(
+            &skeleton,
+            additive_clip,
+            &AdditiveReference::FirstFrame,
+            AdditiveDriver::Parameter(0.0),
+        ));
         Ok(Self {
             base: Base {
                 render_pipeline,
                 model,
                 camera_bind_group,
                 pose_bind_group,
+                morph_bind_group,
                 instance_buffer,
                 animated_buffer,
                 skeleton,
             },
             method: Method::LayeredAnimation(LayeredAnimation {
                 current_pose,
-                add_pose,
-                additive_base,
-                clips,
-                clip_index,
-                additive_index,
+                clip,
+                additive,
                 playback_time: 0.0,
                 additive_time: 0.0,
                 additive_direction: 1.0,
@@ -210,39 +212,23 @@ impl RenderableT for BlenderPlayer {
         /* let delta_time = 0.2; */
         let mut pose_palette = match &mut self.method {
             Method::BlendBetweenClips(BlendBetweenClips {
-                pose,
-                clip_a,
-                clip_b,
-                time_a,
-                time_b,
-                pose_a,
-                pose_b,
-                blend_time,
-                invert_blend,
+                controller,
+                clips,
+                current_clip,
+                fade_timer,
             }) => {
-                *time_a = clip_a.sample(pose_a, *time_a + delta_time);
-                *time_b = clip_b.sample(pose_b, *time_b + delta_time);
-
-                let mut bt = blend_time.clamp(0.0, 1.0);
-                if *invert_blend {
-                    bt = 1.0 - bt;
-                }
-                pose.blend(&pose_a, &pose_b, bt, None);
-                *blend_time += delta_time;
-                if *blend_time > 2.0 {
-                    *blend_time = 0.0;
-                    *invert_blend = !*invert_blend;
-                    *pose = self.base.skeleton.rest_pose.clone();
+                *fade_timer += delta_time;
+                if *fade_timer > 2.0 {
+                    *fade_timer = 0.0;
+                    *current_clip = (*current_clip + 1) % clips.len();
+                    controller.fade_to(clips[*current_clip].clone(), 1.0);
                 }
-                pose.matrix_palette()
+                controller.update(delta_time).matrix_palette()
             }
             Method::LayeredAnimation(LayeredAnimation {
                 current_pose,
-                add_pose,
-                additive_base,
-                clips,
-                clip_index,
-                additive_index,
+                clip,
+                additive,
                 playback_time,
                 additive_time,
                 additive_direction,
@@ -252,14 +238,9 @@ impl RenderableT for BlenderPlayer {
                 if *additive_time == 0.0 || *additive_time == 1.0 {
                     *additive_direction *= -1.0;
                 }
-                *playback_time =
-                    clips[*clip_index].sample(current_pose, *playback_time + delta_time);
-                /* *current_pose = self.base.skeleton.rest_pose.clone(); */
-                let additive_clip = &mut clips[*additive_index];
-                additive_clip.looping = false;
-                let time = additive_clip.start_time + (additive_clip.duration() * *additive_time);
-                additive_clip.sample(add_pose, time);
-                current_pose.add(&current_pose.clone(), add_pose, additive_base, None);
+                *playback_time = clip.sample(current_pose, *playback_time + delta_time);
+                additive.set_parameter(0, *additive_time);
+                additive.apply(current_pose, delta_time);
                 current_pose.matrix_palette()
             }
         };
@@ -285,6 +266,7 @@ impl RenderableT for BlenderPlayer {
             vec![
                 (1, &self.base.camera_bind_group),
                 (2, &self.base.pose_bind_group),
+                (3, &self.base.morph_bind_group),
             ],
         );
         std::result::Result::Ok(())
@@ -20,6 +20,7 @@ pub struct IkLegPlayer {
     left_leg: IkLeg,
     right_leg: IkLeg,
     last_model_y: f32,
+    pelvis_offset: f32,
     toe_length: f32,
     skeletal_model: SkeletalModel,
 }
@@ -61,6 +62,7 @@ impl IkLegPlayer {
             left_leg,
             right_leg,
             last_model_y,
+            pelvis_offset: 0.0,
             toe_length,
             skeletal_model,
         }
@@ -110,6 +112,9 @@ impl RenderableT for IkLegPlayer {
             }
         }
 
+        // Starts from the rest pose as the pelvis offset and the IK below would otherwise pile
+        // up every frame on joints the clip doesn't animate
+        self.current_pose.clone_from(&self.skeleton.rest_pose);
         self.playback_time = self
             .clip
             .sample(&mut self.current_pose, self.playback_time + delta_time);
@@ -133,33 +138,38 @@ impl RenderableT for IkLegPlayer {
         let (mut world_right_ankle, right_ankle_ray, mut predictive_right_ankle) =
             ankle_setup(self.right_leg.ankle_index);
 
+        let mut left_ground_normal = Vec3::Y;
+        let mut right_ground_normal = Vec3::Y;
         let mut ground_reference = self.model.translation;
-        let mut ankle_assignment =
-            |triangle, ankle_ray: &Ray, world_ankle: &mut Vec3, predictive_ankle: &mut Vec3| {
-                if let Some(hit_point) = ankle_ray.cast(triangle) {
-                    if (hit_point - ankle_ray.origin).length_squared() < ray_height * ray_height {
-                        *world_ankle = hit_point;
-                    }
-                    if hit_point.y < ground_reference.y {
-                        ground_reference = hit_point - Vec3::new(0.0, self.sink_into_ground, 0.0);
-                    }
-                    *predictive_ankle = hit_point;
+        let mut ankle_assignment = |ankle_ray: &Ray,
+                                    world_ankle: &mut Vec3,
+                                    predictive_ankle: &mut Vec3,
+                                    ground_normal: &mut Vec3| {
+            // Only the nearest surface counts where the ground overlaps itself
+            if let Some(hit) = ankle_ray.closest_hit(&self.triangles) {
+                let hit_point = hit.point;
+                if (hit_point - ankle_ray.origin).length_squared() < ray_height * ray_height {
+                    *world_ankle = hit_point;
                 }
-            };
-        for triangle in &self.triangles {
-            ankle_assignment(
-                triangle,
-                &left_ankle_ray,
-                &mut world_left_ankle,
-                &mut predictive_left_ankle,
-            );
-            ankle_assignment(
-                triangle,
-                &right_ankle_ray,
-                &mut world_right_ankle,
-                &mut predictive_right_ankle,
-            );
-        }
+                if hit_point.y < ground_reference.y {
+                    ground_reference = hit_point - Vec3::new(0.0, self.sink_into_ground, 0.0);
+                }
+                *predictive_ankle = hit_point;
+                *ground_normal = hit.normal;
+            }
+        };
+        ankle_assignment(
+            &left_ankle_ray,
+            &mut world_left_ankle,
+            &mut predictive_left_ankle,
+            &mut left_ground_normal,
+        );
+        ankle_assignment(
+            &right_ankle_ray,
+            &mut world_right_ankle,
+            &mut predictive_right_ankle,
+            &mut right_ground_normal,
+        );
 
         self.model.translation.y = self.last_model_y;
         self.model.translation = self
@@ -168,10 +178,35 @@ impl RenderableT for IkLegPlayer {
             .lerp(ground_reference, delta_time * 10.0);
         self.last_model_y = self.model.translation.y;
 
-        let mut solve_legs = |world_ankle: &mut Vec3, predictive_ankle, motion, leg: &mut IkLeg| {
-            *world_ankle = world_ankle.lerp(predictive_ankle, motion);
+        world_left_ankle = world_left_ankle.lerp(predictive_left_ankle, left_motion);
+        world_right_ankle = world_right_ankle.lerp(predictive_right_ankle, right_motion);
+
+        // Lowers the pelvis so the leg standing lower still reaches the ground
+        let pelvis_offset = self
+            .left_leg
+            .pelvis_offset(&self.model, &self.current_pose, world_left_ankle)
+            .max(
+                self.right_leg
+                    .pelvis_offset(&self.model, &self.current_pose, world_right_ankle),
+            );
+        self.pelvis_offset += (pelvis_offset - self.pelvis_offset) * (delta_time * 10.0).min(1.0);
+        if let Some(pelvis) = self.current_pose.parent(self.left_leg.hip_index) {
+            let parent_world = match self.current_pose.parent(pelvis) {
+                Some(parent) => self
+                    .model
+                    .combine(&self.current_pose.global_transform(parent)),
+                None => self.model.clone(),
+            };
+            let mut pelvis_local = self.current_pose.local_transform(pelvis).clone();
+            pelvis_local.translation += parent_world.rotation.inverse()
+                * Vec3::new(0.0, -self.pelvis_offset, 0.0)
+                / parent_world.scale;
+            self.current_pose.set_local_transform(pelvis, pelvis_local);
+        }
 
+        let mut solve_legs = |world_ankle: &mut Vec3, ground_normal, motion, leg: &mut IkLeg| {
             leg.solve(&self.model, &self.current_pose, *world_ankle);
+            leg.align_foot(&self.model, ground_normal, motion);
             self.current_pose.blend(
                 &self.current_pose.clone(),
                 &leg.ik_pose,
@@ -204,7 +239,7 @@ impl RenderableT for IkLegPlayer {
             left_ankle_world,
         ) = solve_legs(
             &mut world_left_ankle,
-            predictive_left_ankle,
+            left_ground_normal,
             left_motion,
             &mut self.left_leg,
         );
@@ -216,7 +251,7 @@ impl RenderableT for IkLegPlayer {
             right_ankle_world,
         ) = solve_legs(
             &mut world_right_ankle,
-            predictive_right_ankle,
+            right_ground_normal,
             right_motion,
             &mut self.right_leg,
         );
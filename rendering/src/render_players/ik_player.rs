@@ -3,11 +3,11 @@ use crate::{
     point::PointRender,
     renderable::{RenderableT, SimpleVertex},
 };
-use animation::{fabrik_solver::FabrikSolver, transform_track::TransformTrack};
+use animation::{ik_solver::IkSolver, transform_track::TransformTrack};
 use math::glam_transform::Transform;
 
 pub struct IkPlayer {
-    solver: FabrikSolver,
+    solver: Box<dyn IkSolver>,
     target_path: TransformTrack,
     solver_point_renderer: PointRender,
     solver_line_renderer: LineRender,
@@ -17,13 +17,13 @@ pub struct IkPlayer {
 
 impl IkPlayer {
     pub fn new(
-        solver: FabrikSolver,
+        solver: impl IkSolver + 'static,
         target_path: TransformTrack,
         solver_point_renderer: PointRender,
         solver_line_renderer: LineRender,
     ) -> Self {
         Self {
-            solver,
+            solver: Box::new(solver),
             target_path,
             target: Transform::default(),
             play_time: 0.0,
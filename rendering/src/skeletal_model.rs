@@ -4,9 +4,9 @@ use crate::{
 };
 use animation::{clip::Clip, pose::Pose, skeleton::Skeleton};
 use bytemuck::{Pod, Zeroable};
-use glam::Mat4;
+use glam::{Mat4, Vec3};
 use gltf::Material;
-use math::{matrix4::Matrix4, vector3::Vector3};
+use math::{dual_quat::DualQuat, matrix4::Matrix4, vector3::Vector3};
 use num_traits::Zero;
 use std::{
     mem::size_of,
@@ -73,19 +73,113 @@ impl Vertex for SkeletalVertex {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SkinningMode {
+    #[default]
+    Linear,
+    /// Keeps volume around twisting joints, but ignores joint scale
+    DualQuaternion,
+}
+
+pub const MAX_MORPH_TARGETS: usize = 32;
+
+#[derive(Debug, Clone, Default)]
+pub struct MorphTarget {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+}
+
+/// Morph targets of a mesh together with the node whose weights are animated
+#[derive(Debug, Clone, Default)]
+pub struct MorphTargets {
+    pub node: Option<u32>,
+    pub targets: Vec<MorphTarget>,
+    pub weights: Vec<f32>,
+}
+
+impl MorphTargets {
+    pub fn morph_vertex(
+        &self,
+        index: usize,
+        position: Vec3,
+        normal: Vec3,
+        weights: &[f32],
+    ) -> (Vec3, Vec3) {
+        let mut position = position;
+        let mut normal = normal;
+        for (target, &weight) in self.targets.iter().zip(weights) {
+            if weight == 0.0 {
+                continue;
+            }
+            if let Some(delta) = target.positions.get(index) {
+                position += Vec3::from(*delta) * weight;
+            }
+            if let Some(delta) = target.normals.get(index) {
+                normal += Vec3::from(*delta) * weight;
+            }
+        }
+        (position, normal.normalize_or_zero())
+    }
+
+    /// Position and normal deltas laid out target by target, every vertex a position delta
+    /// followed by a normal delta, padded to a vec4 each for the shader
+    fn deltas(&self, vertex_count: usize) -> Vec<[f32; 4]> {
+        let mut deltas = vec![[0.0; 4]; (self.targets.len() * vertex_count * 2).max(1)];
+        for (t, target) in self.targets.iter().enumerate() {
+            for (i, delta) in target.positions.iter().take(vertex_count).enumerate() {
+                deltas[(t * vertex_count + i) * 2] = [delta[0], delta[1], delta[2], 0.0];
+            }
+            for (i, delta) in target.normals.iter().take(vertex_count).enumerate() {
+                deltas[(t * vertex_count + i) * 2 + 1] = [delta[0], delta[1], delta[2], 0.0];
+            }
+        }
+        deltas
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct MorphUniform {
+    vertex_count: u32,
+    target_count: u32,
+    _padding: [u32; 2],
+    weights: [[f32; 4]; MAX_MORPH_TARGETS / 4],
+}
+
+impl MorphUniform {
+    pub fn new(vertex_count: usize, weights: &[f32]) -> Self {
+        let mut result = Self {
+            vertex_count: vertex_count as u32,
+            target_count: weights.len().min(MAX_MORPH_TARGETS) as u32,
+            _padding: [0; 2],
+            weights: [[0.0; 4]; MAX_MORPH_TARGETS / 4],
+        };
+        for (i, &weight) in weights.iter().take(MAX_MORPH_TARGETS).enumerate() {
+            result.weights[i / 4][i % 4] = weight;
+        }
+        result
+    }
+}
+
 pub struct SkeletalModel {
     render_pipeline: RenderPipeline,
+    dual_quat_render_pipeline: RenderPipeline,
     model: Model<SkeletalVertex>,
     camera_bind_group: BindGroup,
     pose_bind_group: BindGroup,
+    morph_bind_group: BindGroup,
     original_positions: Vec<[f32; 3]>,
     original_normals: Vec<[f32; 3]>,
     pub instance_buffer: wgpu::Buffer,
     pub animated_buffer: wgpu::Buffer,
+    pub morph_buffer: wgpu::Buffer,
+    morph_targets: MorphTargets,
+    morph_weights: Vec<f32>,
     animated_pose: Pose,
     clip: Clip,
     skeleton: Skeleton,
     playback_time: f32,
+    pub skinning_mode: SkinningMode,
 }
 
 impl SkeletalModel {
@@ -103,16 +197,20 @@ impl SkeletalModel {
         clip: Clip,
         skeleton: Skeleton,
         instances: Arc<RwLock<Vec<Instance>>>,
+        morph_targets: MorphTargets,
     ) -> Result<Self> {
         let SkeletalModelBase {
             render_pipeline,
+            dual_quat_render_pipeline,
             model,
             camera_bind_group,
             pose_bind_group,
+            morph_bind_group,
             original_positions,
             original_normals,
             instance_buffer,
             animated_buffer,
+            morph_buffer,
         } = {
             let instances = instances.read().unwrap();
             new_skeletal_pipeline(
@@ -127,22 +225,29 @@ impl SkeletalModel {
                 &material,
                 diffuse_texture,
                 &instances,
+                &morph_targets,
             )
         };
 
         Ok(Self {
             render_pipeline,
+            dual_quat_render_pipeline,
             model,
             camera_bind_group,
             pose_bind_group,
+            morph_bind_group,
             original_positions,
             original_normals,
             instance_buffer,
             animated_buffer,
+            morph_buffer,
+            morph_weights: morph_targets.weights.clone(),
+            morph_targets,
             animated_pose: skeleton.rest_pose.clone(),
             clip,
             skeleton,
             playback_time: 0.0,
+            skinning_mode: SkinningMode::Linear,
         })
     }
 
@@ -152,7 +257,42 @@ impl SkeletalModel {
         }
         let time = self.playback_time + delta_time;
         self.playback_time = self.clip.sample(&mut self.animated_pose, time);
+        self.sample_morph_weights();
+
+        // The vertices are skinned already so the shader is given a palette that leaves them be
+        let joint_count = self.animated_pose.len();
+        match self.skinning_mode {
+            SkinningMode::Linear => {
+                self.cpu_skin_linear();
+                queue.write_buffer(
+                    &self.animated_buffer,
+                    0,
+                    bytemuck::cast_slice(&vec![Mat4::IDENTITY; joint_count]),
+                );
+            }
+            SkinningMode::DualQuaternion => {
+                self.cpu_skin_dual_quat();
+                queue.write_buffer(
+                    &self.animated_buffer,
+                    0,
+                    bytemuck::cast_slice(&vec![DualQuat::IDENTITY; joint_count]),
+                );
+            }
+        }
+        queue.write_buffer(
+            &self.model.meshes[0].vertex_buffer,
+            0,
+            bytemuck::cast_slice(&self.model.meshes[0].model_vertices),
+        );
+        // The morph targets are applied above as well so the shader mustn't add them again
+        queue.write_buffer(
+            &self.morph_buffer,
+            0,
+            bytemuck::cast_slice(&[MorphUniform::new(self.original_positions.len(), &[])]),
+        );
+    }
 
+    fn cpu_skin_linear(&mut self) {
         let pose_palette = self.animated_pose.matrix_palette();
 
         for (i, vertex) in &mut self.model.meshes[0].model_vertices.iter_mut().enumerate() {
@@ -169,33 +309,92 @@ impl SkeletalModel {
                 * w[3];
 
             let skin = m0 + m1 + m2 + m3;
-            vertex.position = skin
-                .transform_point3(self.original_positions[i].into())
-                .into();
-            vertex.normal = skin
-                .transform_vector3(self.original_normals[i].into())
-                .into();
+            let (position, normal) = self.morph_targets.morph_vertex(
+                i,
+                self.original_positions[i].into(),
+                self.original_normals[i].into(),
+                &self.morph_weights,
+            );
+            vertex.position = skin.transform_point3(position).into();
+            vertex.normal = skin.transform_vector3(normal).into();
+        }
+    }
+
+    fn cpu_skin_dual_quat(&mut self) {
+        let pose_palette = self.skeleton.dual_quat_skin_palette(&self.animated_pose);
+
+        for (i, vertex) in &mut self.model.meshes[0].model_vertices.iter_mut().enumerate() {
+            let j = vertex.joints;
+            let w = vertex.weights;
+
+            // Flip joints to the hemisphere of the first one so the rotations don't cancel out
+            let first = pose_palette[j[0] as usize];
+            let skin = j
+                .iter()
+                .zip(w)
+                .map(|(joint, weight)| {
+                    let dq = pose_palette[*joint as usize];
+                    if first.dot(&dq) < 0.0 {
+                        dq * -weight
+                    } else {
+                        dq * weight
+                    }
+                })
+                .reduce(|acc, dq| acc + dq)
+                .unwrap()
+                .normalize();
+            let (position, normal) = self.morph_targets.morph_vertex(
+                i,
+                self.original_positions[i].into(),
+                self.original_normals[i].into(),
+                &self.morph_weights,
+            );
+            vertex.position = skin.transform_point(position).into();
+            vertex.normal = skin.transform_vector(normal).into();
         }
-        queue.write_buffer(
-            &self.model.meshes[0].vertex_buffer,
-            0,
-            bytemuck::cast_slice(&self.model.meshes[0].model_vertices),
-        );
     }
 
     fn gpu_skin(&mut self, delta_time: f32, queue: &wgpu::Queue) {
         let time = self.playback_time + delta_time;
         self.playback_time = self.clip.sample(&mut self.animated_pose, time);
-        let mut pose_palette: Vec<Mat4> = self.animated_pose.matrix_palette();
-        for (i, p) in pose_palette.iter_mut().enumerate() {
-            *p = *p * self.skeleton.inverse_bind_pose()[i];
+        match self.skinning_mode {
+            SkinningMode::Linear => {
+                let mut pose_palette: Vec<Mat4> = self.animated_pose.matrix_palette();
+                for (i, p) in pose_palette.iter_mut().enumerate() {
+                    *p = *p * self.skeleton.inverse_bind_pose()[i];
+                }
+                queue.write_buffer(
+                    &self.animated_buffer,
+                    0,
+                    bytemuck::cast_slice(&pose_palette),
+                );
+            }
+            SkinningMode::DualQuaternion => {
+                let pose_palette = self.skeleton.dual_quat_skin_palette(&self.animated_pose);
+                queue.write_buffer(
+                    &self.animated_buffer,
+                    0,
+                    bytemuck::cast_slice(&pose_palette),
+                );
+            }
         }
+        self.sample_morph_weights();
         queue.write_buffer(
-            &self.animated_buffer,
+            &self.morph_buffer,
             0,
-            bytemuck::cast_slice(&pose_palette),
+            bytemuck::cast_slice(&[MorphUniform::new(
+                self.original_positions.len(),
+                &self.morph_weights,
+            )]),
         );
     }
+
+    fn sample_morph_weights(&mut self) {
+        if let Some(node) = self.morph_targets.node {
+            self.clip
+                .sample_weights(node, &mut self.morph_weights, self.playback_time);
+        }
+    }
 }
 
 impl RenderableT for SkeletalModel {
@@ -214,11 +413,18 @@ impl RenderableT for SkeletalModel {
         render_pass: &'a mut wgpu::RenderPass<'b>,
     ) -> Result<(), wgpu::SurfaceError> {
         render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_pipeline(match self.skinning_mode {
+            SkinningMode::Linear => &self.render_pipeline,
+            SkinningMode::DualQuaternion => &self.dual_quat_render_pipeline,
+        });
         render_pass.draw_model_instanced(
             &self.model,
             0..1,
-            vec![(1, &self.camera_bind_group), (2, &self.pose_bind_group)],
+            vec![
+                (1, &self.camera_bind_group),
+                (2, &self.pose_bind_group),
+                (3, &self.morph_bind_group),
+            ],
         );
         std::result::Result::Ok(())
     }
@@ -226,13 +432,16 @@ impl RenderableT for SkeletalModel {
 
 pub struct SkeletalModelBase {
     pub render_pipeline: RenderPipeline,
+    pub dual_quat_render_pipeline: RenderPipeline,
     pub model: Model<SkeletalVertex>,
     pub camera_bind_group: BindGroup,
     pub pose_bind_group: BindGroup,
+    pub morph_bind_group: BindGroup,
     pub original_positions: Vec<[f32; 3]>,
     pub original_normals: Vec<[f32; 3]>,
     pub instance_buffer: wgpu::Buffer,
     pub animated_buffer: wgpu::Buffer,
+    pub morph_buffer: wgpu::Buffer,
 }
 
 pub fn new_skeletal_pipeline<'a>(
@@ -247,8 +456,11 @@ pub fn new_skeletal_pipeline<'a>(
     material: &Material<'a>,
     diffuse_texture: Arc<RwLock<texture::Texture>>,
     instances: &Vec<Instance>,
+    morph_targets: &MorphTargets,
 ) -> SkeletalModelBase {
     let shader = device.create_shader_module(wgpu::include_wgsl!("skeletal_model.wgsl"));
+    let dual_quat_shader =
+        device.create_shader_module(wgpu::include_wgsl!("skeletal_model_dual_quat.wgsl"));
     let animated_buffer = device.create_buffer_init(&BufferInitDescriptor {
         label: Some("animated_buffer"),
         contents: bytemuck::cast_slice(&[Matrix4::identity(); 120]),
@@ -300,55 +512,113 @@ pub fn new_skeletal_pipeline<'a>(
         }],
     });
 
+    let morph_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("morph_buffer"),
+        contents: bytemuck::cast_slice(&[MorphUniform::new(
+            original_positions.len(),
+            &morph_targets.weights,
+        )]),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let morph_deltas_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("morph_deltas_buffer"),
+        contents: bytemuck::cast_slice(&morph_targets.deltas(original_positions.len())),
+        usage: BufferUsages::STORAGE,
+    });
+    let morph_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("morph_bind_group_layout"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                count: None,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                visibility: ShaderStages::VERTEX,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                count: None,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                visibility: ShaderStages::VERTEX,
+            },
+        ],
+    });
+    let morph_bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("morph_bind_group"),
+        layout: &morph_bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: morph_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: morph_deltas_buffer.as_entire_binding(),
+            },
+        ],
+    });
+
     let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Render pipeline layout"),
         bind_group_layouts: &[
             &texture_bind_group_layout,
             &camera_bind_group_layout,
             &pose_bind_group_layout,
+            &morph_bind_group_layout,
         ],
         push_constant_ranges: &[],
     });
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render pipeline"),
-        layout: Some(&render_pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: &[SkeletalVertex::desc(), InstanceRaw::desc()],
-        },
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            polygon_mode: wgpu::PolygonMode::Fill,
-            unclipped_depth: false,
-            conservative: false,
-        },
-        depth_stencil: Some(DepthStencilState {
-            format: texture::Texture::DEPTH_FORMAT,
-            depth_write_enabled: true,
-            depth_compare: CompareFunction::Less,
-            stencil: StencilState::default(),
-            bias: DepthBiasState::default(),
-        }),
-        multisample: MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: config.format,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: ColorWrites::all(),
-            })],
-        }),
-        multiview: None,
-    });
+    let create_pipeline = |shader: &wgpu::ShaderModule| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[SkeletalVertex::desc(), InstanceRaw::desc()],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+        })
+    };
+    let render_pipeline = create_pipeline(&shader);
+    let dual_quat_render_pipeline = create_pipeline(&dual_quat_shader);
 
     let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
     let instance_buffer = device.create_buffer_init(&BufferInitDescriptor {
@@ -401,12 +671,15 @@ pub fn new_skeletal_pipeline<'a>(
     };
     SkeletalModelBase {
         render_pipeline,
+        dual_quat_render_pipeline,
         model,
         camera_bind_group,
         pose_bind_group,
+        morph_bind_group,
         original_positions,
         original_normals,
         instance_buffer,
         animated_buffer,
+        morph_buffer,
     }
 }
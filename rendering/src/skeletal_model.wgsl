@@ -19,6 +19,17 @@ struct Pose {
 @group(2) @binding(0)
 var<uniform> animated_pose: Pose;
 
+struct Morph {
+    vertex_count: u32,
+    target_count: u32,
+    weights: array<vec4<f32>, 8>
+}
+
+@group(3) @binding(0)
+var<uniform> morph: Morph;
+@group(3) @binding(1)
+var<storage, read> morph_deltas: array<vec4<f32>>;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
@@ -29,11 +40,21 @@ struct VertexInput {
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) normal: vec3<f32>
 }
 
 @vertex
-fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+fn vs_main(@builtin(vertex_index) vertex_index: u32, model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var position: vec3<f32> = model.position;
+    var normal: vec3<f32> = model.normal;
+    // Every vertex of a target has a position delta followed by a normal delta
+    for (var i: u32 = 0u; i < morph.target_count; i = i + 1u) {
+        let weight = morph.weights[i / 4u][i % 4u];
+        let delta = (i * morph.vertex_count + vertex_index) * 2u;
+        position = position + morph_deltas[delta].xyz * weight;
+        normal = normal + morph_deltas[delta + 1u].xyz * weight;
+    }
     var skin: mat4x4<f32> = animated_pose.data[model.joints.x] * model.weights.x
                             + animated_pose.data[model.joints.y] * model.weights.y
                             + animated_pose.data[model.joints.z] * model.weights.z
@@ -44,7 +65,8 @@ fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
                                    instance.model_matrix3);
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_proj * model_matrix * skin * vec4<f32>(model.position, 1.0);
+    out.normal = normalize((model_matrix * skin * vec4<f32>(normal, 0.0)).xyz);
+    out.clip_position = camera.view_proj * model_matrix * skin * vec4<f32>(position, 1.0);
     return out;
 }
 
@@ -0,0 +1,112 @@
+struct InstanceInput {
+    @location(5) model_matrix0: vec4<f32>,
+    @location(6) model_matrix1: vec4<f32>,
+    @location(7) model_matrix2: vec4<f32>,
+    @location(8) model_matrix3: vec4<f32>
+}
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct DualQuat {
+    real: vec4<f32>,
+    dual: vec4<f32>
+}
+
+struct Pose {
+    data: array<DualQuat, 120>
+}
+
+@group(2) @binding(0)
+var<uniform> animated_pose: Pose;
+
+struct Morph {
+    vertex_count: u32,
+    target_count: u32,
+    weights: array<vec4<f32>, 8>
+}
+
+@group(3) @binding(0)
+var<uniform> morph: Morph;
+@group(3) @binding(1)
+var<storage, read> morph_deltas: array<vec4<f32>>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) weights: vec4<f32>,
+    @location(4) joints: vec4<u32>
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) normal: vec3<f32>
+}
+
+// Blends in the hemisphere of the first joint so opposite signed rotations don't cancel out
+fn blend_dual_quats(joints: vec4<u32>, weights: vec4<f32>) -> DualQuat {
+    let first = animated_pose.data[joints.x];
+    var real = vec4<f32>(0.0);
+    var dual = vec4<f32>(0.0);
+    for (var i: i32 = 0; i < 4; i = i + 1) {
+        let dq = animated_pose.data[joints[i]];
+        let weight = select(weights[i], -weights[i], dot(first.real, dq.real) < 0.0);
+        real = real + dq.real * weight;
+        dual = dual + dq.dual * weight;
+    }
+    let length = length(real);
+    return DualQuat(real / length, dual / length);
+}
+
+fn transform_point(dq: DualQuat, p: vec3<f32>) -> vec3<f32> {
+    let r = dq.real;
+    let d = dq.dual;
+    let rotated = p + 2.0 * cross(r.xyz, cross(r.xyz, p) + r.w * p);
+    let translation = 2.0 * (r.w * d.xyz - d.w * r.xyz + cross(r.xyz, d.xyz));
+    return rotated + translation;
+}
+
+fn transform_vector(dq: DualQuat, v: vec3<f32>) -> vec3<f32> {
+    let r = dq.real;
+    return v + 2.0 * cross(r.xyz, cross(r.xyz, v) + r.w * v);
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var position: vec3<f32> = model.position;
+    var normal: vec3<f32> = model.normal;
+    // Every vertex of a target has a position delta followed by a normal delta
+    for (var i: u32 = 0u; i < morph.target_count; i = i + 1u) {
+        let weight = morph.weights[i / 4u][i % 4u];
+        let delta = (i * morph.vertex_count + vertex_index) * 2u;
+        position = position + morph_deltas[delta].xyz * weight;
+        normal = normal + morph_deltas[delta + 1u].xyz * weight;
+    }
+    let skin = blend_dual_quats(model.joints, model.weights);
+    let model_matrix = mat4x4<f32>(instance.model_matrix0,
+                                   instance.model_matrix1,
+                                   instance.model_matrix2,
+                                   instance.model_matrix3);
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.normal = normalize((model_matrix * vec4<f32>(transform_vector(skin, normal), 0.0)).xyz);
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(transform_point(skin, position), 1.0);
+    return out;
+}
+
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse,  in.tex_coords);
+}
\ No newline at end of file